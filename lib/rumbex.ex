@@ -10,49 +10,690 @@ defmodule Rumbex do
   while handles are open.
   """
 
+  require Logger
+
+  alias Rumbex.Operations
   alias Rumbex.Path
   alias Rumbex.Pool
   alias Rumbex.PoolSupervisor
 
   @registry Rumbex.Registry
+  # CreateOptions bit: don't recall offline (HSM) file data on open
+  @file_open_no_recall 0x0040_0000
+  # CreateOptions bit: open with backup semantics (needs SeBackupPrivilege server-side)
+  @file_open_for_backup_intent 0x0000_4000
+  # Access mask bit: grant whatever the caller is entitled to
+  @maximum_allowed 0x0200_0000
+  @snapshot_token ~r/\A@GMT-\d{4}\.\d{2}\.\d{2}-\d{2}\.\d{2}\.\d{2}\z/
 
   # ───────── Public functions ─────────
 
   @doc """
   (Optional) Explicitly prepare a pool for (url_or_unc, username, password).
   You don't need to call this — any operations below will call this automatically.
+  `url_or_unc` is `"smb://host/share"`, `"\\\\host\\share"` or the URL-style UNC
+  `"//host/share"`, here and in every other function.
+
+  Options:
+  * `:size` — number of connections in the pool (default 5)
+  * `:channels` — connections per pooled `Conn` to stripe large reads/writes across;
+    only used when the server advertises multichannel (default 1)
+  * `:initial_credits` / `:max_credits` — SMB2 credit window, i.e. how many requests
+    may be in flight (1..8192). Unset keeps the client default; 256–512 helps
+    pipelining on high-latency, high-bandwidth links. The server may grant less.
+  * `:idle_timeout_ms` — log a pooled `Conn` off (closing its socket) after this long
+    without use; the next operation on it reconnects transparently (default: never)
+  * `:client_name` — workstation name reported during session setup (1..15 chars of
+    letters, digits, `-` and `_`; default: the OS hostname)
+  * `:kerberos` — authenticate with Kerberos only (`username` is the principal, e.g.
+    `"svc@EXAMPLE.COM"`); needs the NIF built with the `kerberos` feature, otherwise
+    connecting fails with `:kerberos_unavailable`. `KRB5_CONFIG` (or `/etc/krb5.conf`)
+    must describe the realm; `url_or_unc` must use the host name the KDC knows
+  * `:keytab` / `:ccache` — not supported: the Kerberos client gets its ticket with the
+    password and reads no keytab or credential cache, so either is `{:error, :unsupported}`
+  * `:nt_hash` — authenticate over NTLM with this 16-byte NT hash instead of a password
+    (pass-the-hash). Any other size fails with `{:error, :bad_hash}`. `:domain`
+    optionally names the account's domain. `password` isn't sent but still keys the
+    pool, so later calls must pass the same placeholder as this `connect/4`
+  * `:handle_cache` — per pooled `Conn`, keep up to this many read handles open so
+    repeated `read_file` calls on the same hot path skip the open/close round-trips
+    (max 64, default 0 = off). Writes, renames and deletes made through the pool drop
+    the affected handle; a cached handle may keep other clients from opening the file
+    exclusively
+  * `:max_read_bytes` — whole-file reads (`read_file`, `read_files`, `read_file_by_id`
+    and friends) of a file bigger than this fail with `{:error, {:too_large, size}}`
+    before anything is allocated (default 0 = no cap)
+  * `:transport` — `:direct_tcp` (port 445), the default and for now the only transport:
+    the `smb` crate has no NetBIOS session service yet, so `:netbios` (port 139) fails
+    with `{:error, :transport_unsupported}`
+  * `:timeout_ms` — fail any request the server hasn't answered within this time
+    instead of waiting forever (default 0 = no timeout); see `set_timeout/4`
+  * `:cancel` — a token from `cancel_token/0`: calling `cancel/1` on it from another
+    process returns `{:error, :cancelled}` at once. A TCP connect still in progress
+    (e.g. to an unreachable host) is aborted and its socket closed; a handshake already
+    under way is left to a background thread that stops at its next step and drops it
+
+  Connection options only apply when the pool is started by this call.
+
+  Fails with `{:error, :share_not_found}` when the share doesn't exist and
+  `{:error, :auth_failed}` when the server rejects the credentials.
   """
   @spec connect(String.t(), String.t(), String.t(), keyword()) :: :ok | {:error, term()}
   def connect(url_or_unc, username, password, opts \\ []) do
-    with {:ok, _name} <- ensure_pool(url_or_unc, username, password, Keyword.get(opts, :size, 5)) do
+    {size, conn_opts} = Keyword.pop(opts, :size, 5)
+
+    with {:ok, _name} <- ensure_pool(url_or_unc, username, password, size, Map.new(conn_opts)) do
       :ok
     end
   end
 
-  @spec list_dir(String.t(), String.t(), String.t(), String.t()) ::
-          {:ok, [{String.t(), :file | :directory}]} | {:error, term()}
-  def list_dir(url_or_unc, username, password, path \\ "/"),
-    do: call_pool(url_or_unc, username, password, {:list_dir, path})
+  @doc """
+  List the shares exported by a server, without connecting to any of them.
+  Accepts `"smb://host"`, `"\\\\host"` or a bare host name. Does not use a pool.
+  """
+  @spec list_shares(String.t(), String.t(), String.t()) ::
+          {:ok, [String.t()]} | {:error, term()}
+  def list_shares(url_or_host, username, password) do
+    host = Path.parse_host!(url_or_host)
 
-  @spec read_file(String.t(), String.t(), String.t(), String.t()) ::
-          {:ok, binary()} | {:error, term()}
-  def read_file(url_or_unc, username, password, path),
-    do: call_pool(url_or_unc, username, password, {:read_file, path})
+    with {:ok, session} <- Operations.authenticate(host, username, password) do
+      Operations.list_shares(session)
+    end
+  rescue
+    e in ArgumentError -> {:error, e.message}
+  end
 
-  @spec write_file(String.t(), String.t(), String.t(), String.t(), iodata()) ::
-          {:ok, non_neg_integer()} | {:error, term()}
-  def write_file(url_or_unc, username, password, path, data),
-    do: call_pool(url_or_unc, username, password, {:write_file, path, data})
+  @doc """
+  SMB2 NEGOTIATE with a server, without credentials and without a pool. Accepts the
+  host forms of `list_shares/3`.
+
+  Returns `{:ok, info}` with `:guid`, `:dialect` (the one the server picks from those
+  this client offers), `:signing_required`, `:encryption` (supported) and the
+  capability flags and max sizes of `server_info/3`. `:encryption_required` is always
+  `nil`: servers only decide that at session setup. A port that accepts connections
+  but doesn't answer with a valid NEGOTIATE within a few seconds gives
+  `{:error, :not_smb}`.
+  """
+  @spec probe(String.t(), :inet.port_number()) :: {:ok, map()} | {:error, :not_smb | term()}
+  def probe(url_or_host, port \\ 445) do
+    # a port in an smb:// url wins over `port`
+    case String.split(Path.parse_host!(url_or_host), ":", parts: 2) do
+      [host, url_port] -> Operations.probe(host, String.to_integer(url_port))
+      [host] -> Operations.probe(host, port)
+    end
+  rescue
+    e in ArgumentError -> {:error, e.message}
+  end
+
+  @doc """
+  List a directory. If enumeration fails partway (e.g. a transient error in a huge
+  directory), the entries read so far come back as `{:partial, entries, reason}`.
+  Entries are read as `FileIdFullDirectoryInformation`, which never makes the server
+  compute 8.3 short names; `list_dir_class/5` picks another class. Listing a file
+  gives `{:error, :not_a_directory}`, as `read_file/5` on a directory gives
+  `{:error, :is_a_directory}`.
+
+  Pass `snapshot: "@GMT-YYYY.MM.DD-HH.MM.SS"` (see `list_snapshots/4`) to list the
+  directory as it was in that shadow copy; a malformed token gives
+  `{:error, :bad_snapshot}`.
+  """
+  @spec list_dir(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          {:ok, [{String.t(), :file | :directory}]}
+          | {:partial, [{String.t(), :file | :directory}], String.t()}
+          | {:error, :bad_snapshot | :not_a_directory | term()}
+  def list_dir(url_or_unc, username, password, path \\ "/", opts \\ []) do
+    with_snapshot(path, opts, fn path ->
+      call_pool(url_or_unc, username, password, {:list_dir, path})
+    end)
+  end
+
+  @doc """
+  Same as `list_dir/4`, but also returns the number of directory records that had
+  to be skipped (corrupted, or a name that isn't valid UTF-16 and so couldn't be
+  reopened). A non-zero count means the listing is incomplete. An enumeration that
+  broke off yields `{:partial, entries, skipped, reason}`.
+  """
+  @spec list_dir_counted(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, [{String.t(), :file | :directory}], non_neg_integer()}
+          | {:partial, [{String.t(), :file | :directory}], non_neg_integer(), String.t()}
+          | {:error, term()}
+  def list_dir_counted(url_or_unc, username, password, path \\ "/"),
+    do: call_pool(url_or_unc, username, password, {:list_dir_counted, path})
+
+  @doc """
+  Count the entries of a directory matching `pattern` (a server-side wildcard such as
+  `"*.log"`; default `"*"`), `.` and `..` excluded, without transferring more than the
+  names. Returns `{:ok, count, skipped}` with `skipped` as for `list_dir_counted/4`
+  (not part of `count`), or `{:partial, count, skipped, reason}` if enumeration broke
+  off.
+  """
+  @spec count_entries(String.t(), String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, non_neg_integer(), non_neg_integer()}
+          | {:partial, non_neg_integer(), non_neg_integer(), String.t()}
+          | {:error, term()}
+  def count_entries(url_or_unc, username, password, path \\ "/", pattern \\ "*"),
+    do: call_pool(url_or_unc, username, password, {:count_entries, path, pattern})
+
+  @doc """
+  Just the names of a directory's entries matching `pattern` (default `"*"`), `.` and
+  `..` excluded. Uses the smallest directory-information class, so on very large
+  directories it moves far fewer bytes than `list_dir/4`; fetch attributes later for
+  the names that need them. `{:partial, names, reason}` if enumeration broke off.
+  """
+  @spec list_names(String.t(), String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, [String.t()]} | {:partial, [String.t()], String.t()} | {:error, term()}
+  def list_names(url_or_unc, username, password, path \\ "/", pattern \\ "*"),
+    do: call_pool(url_or_unc, username, password, {:list_names, path, pattern})
+
+  @doc """
+  Like `list_dir/4`, but each entry also carries its 8.3 short name:
+  `{long_name, short_name, kind}`, with `short_name` `nil` when the entry has none
+  (the name is already 8.3, or short names are disabled on the volume). Useful to map
+  legacy `PROGRA~1`-style references back to real names.
+  """
+  @spec list_dir_both_names(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, [{String.t(), String.t() | nil, :file | :directory}]}
+          | {:partial, [{String.t(), String.t() | nil, :file | :directory}], String.t()}
+          | {:error, term()}
+  def list_dir_both_names(url_or_unc, username, password, path \\ "/"),
+    do: call_pool(url_or_unc, username, password, {:list_dir_both_names, path})
+
+  @doc """
+  `list_dir/4` with an explicit directory-information class, trading detail for
+  enumeration cost:
+
+    * `:id_full` - what `list_dir/4` uses: attributes and file ids, no short names
+    * `:full` - attributes without file ids
+    * `:both` - attributes plus 8.3 short names (slow on some servers; the short
+      names themselves come from `list_dir_both_names/4`)
+    * `:names_only` - the smallest records; no attributes, so every kind is `nil`
+
+  Any other value gives `{:error, :bad_class}`.
+  """
+  @spec list_dir_class(String.t(), String.t(), String.t(), String.t(), atom()) ::
+          {:ok, [{String.t(), :file | :directory | nil}]}
+          | {:partial, [{String.t(), :file | :directory | nil}], String.t()}
+          | {:error, :bad_class | term()}
+  def list_dir_class(url_or_unc, username, password, path, class) when is_atom(class),
+    do: call_pool(url_or_unc, username, password, {:list_dir_class, path, class})
+
+  def list_dir_class(_url_or_unc, _username, _password, _path, _class),
+    do: {:error, :bad_class}
+
+  @doc """
+  Walk the whole tree under `root`, listing directories on several SMB sessions at
+  once — much faster than a recursive `list_dir/4` on deep, high-latency trees.
+
+  `pid` receives `{:entry, path, :file | :directory}` for every entry below `root`
+  (paths relative to the share, `/`-separated), each exactly once and in no
+  particular order, plus `{:walk_error, dir, reason}` for a directory that could not
+  be listed (the walk carries on). Junctions and other reparse points are reported
+  but not descended into; a directory reached a second time under another path (a
+  loop exported by the server, e.g. a bind mount) is sent as `{:cycle_detected, path}`
+  and not walked again. The call returns `:ok` when the walk is complete, or
+  `{:error, :cancelled}` if `pid` exits first.
+
+  Options:
+  * `:workers` — concurrent sessions (1..16, default 4); all but one are opened just for
+    this walk
+  * `:skip_offline` — files with `FILE_ATTRIBUTE_OFFLINE` (tiered away on an HSM share)
+    are sent as `{:offline, path}` instead of `{:entry, path, :file}`, so a consumer
+    doesn't recall them by opening them (default false)
+  """
+  @spec walk_parallel(String.t(), String.t(), String.t(), String.t(), pid(), keyword()) ::
+          :ok | {:error, :cancelled | term()}
+  def walk_parallel(url_or_unc, username, password, root, pid, opts \\ []) do
+    workers = Keyword.get(opts, :workers, 4)
+    skip_offline = Keyword.get(opts, :skip_offline, false)
+    call_pool(url_or_unc, username, password, {:walk_parallel, root, pid, workers, skip_offline})
+  end
+
+  @doc """
+  Read a whole file. `{:error, :not_found}` if there is nothing at `path` (or its
+  parent directory is missing), `{:error, :is_a_directory}` if it is a directory. The
+  other reads (`read_file_info/4`, `read_files/4`, `stream_lines/6`, `download_file/6`,
+  `open/7`) report a missing file the same way. A file over the pool's
+  `:max_read_bytes` (see `connect/4`) is `{:error, {:too_large, size}}`.
+
+  Options:
+  * `:sharing_violation_retries` — how many times to retry when the file is briefly
+    held open by another process (`{:error, :sharing_violation}`), default 0, max 10
+  * `:retry_backoff` — delay before the first retry in ms, doubled per attempt and
+    capped at 5s (default 100)
+  * `:snapshot` — an `"@GMT-YYYY.MM.DD-HH.MM.SS"` token from `list_snapshots/4`; reads
+    the file as it was in that shadow copy (`{:error, :bad_snapshot}` if malformed)
+  * `:hint` — read-ahead hint for the server: `:sequential_scan` for large streaming
+    reads, `:random_access` to avoid wasted read-ahead (default none; anything else is
+    `{:error, :bad_hint}`). Not applied when a `:handle_cache` handle is reused
+  """
+  @spec read_file(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          {:ok, binary()}
+          | {:error,
+             :not_found
+             | :sharing_violation
+             | :bad_snapshot
+             | :bad_hint
+             | :is_a_directory
+             | {:too_large, non_neg_integer()}
+             | term()}
+  def read_file(url_or_unc, username, password, path, opts \\ []) do
+    with {:ok, hint} <- hint_bits(Keyword.get(opts, :hint)) do
+      with_snapshot(path, opts, fn path ->
+        request =
+          if hint == 0, do: {:read_file, path}, else: {:read_file_with_options, path, hint}
+
+        with_sharing_retry(opts, fn -> call_pool(url_or_unc, username, password, request) end)
+      end)
+    end
+  end
+
+  @doc """
+  `read_file/5` with the open tunable, still one open+read+close. Without any of the
+  options below it makes exactly the request `read_file/5` does. Options:
+
+  * `:backup_intent` — open with `FILE_OPEN_FOR_BACKUP_INTENT`, so an account holding
+    the backup privilege on the server can read past the file's ACL
+  * `:no_recall` — as for `get_file_stats/5`: don't recall offline (HSM) data
+  * `:sequential_scan` — the `:sequential_scan` read-ahead hint of `read_file/5`
+  * `:snapshot`, `:sharing_violation_retries`, `:retry_backoff` — as for `read_file/5`
+
+  The share mode isn't tunable: the smb client always opens with read, write and delete
+  sharing, as `read_file/5` does.
+  """
+  @spec read_file_opts(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          {:ok, binary()}
+          | {:error,
+             :not_found
+             | :sharing_violation
+             | :bad_snapshot
+             | :is_a_directory
+             | term()}
+  def read_file_opts(url_or_unc, username, password, path, opts \\ []) do
+    options_bits =
+      [
+        {:sequential_scan, 0x4},
+        {:backup_intent, @file_open_for_backup_intent},
+        {:no_recall, @file_open_no_recall}
+      ]
+      |> Enum.filter(fn {opt, _bit} -> opts[opt] end)
+      |> Enum.reduce(0, fn {_opt, bit}, acc -> Bitwise.bor(acc, bit) end)
+
+    with_snapshot(path, opts, fn path ->
+      request =
+        if options_bits == 0,
+          do: {:read_file, path},
+          else: {:read_file_with_options, path, options_bits}
+
+      with_sharing_retry(opts, fn -> call_pool(url_or_unc, username, password, request) end)
+    end)
+  end
+
+  @doc """
+  Write (create or overwrite) a whole file; `{:error, :is_a_directory}` if `path` is an
+  existing directory, `{:error, :not_found}` if its parent directory doesn't exist (as
+  for `write_atomic/5` and `open_writer/5`). A full disk gives `{:error, :no_space}`,
+  as it does for every write (`write_atomic/5`, `handle_write/2`, `flush/1`, `close/1`
+  and `set_info/6` growing a file). Takes the same retry options as `read_file/5`, plus:
+
+  * `:sync` — issue an SMB2 FLUSH before returning, so the data is durable and
+    visible to readers on other nodes (default false)
+  * `:disposition` — what to do about an existing file:
+    * `:overwrite` (default) — create it, or truncate and replace the existing one
+    * `:create_new` — only create; `{:error, :exists}` if it is already there
+    * `:open_if` — create, or write over the start of the existing file without
+      truncating it (a longer old file keeps its tail)
+    * `:supersede` — create, or replace the existing file as a new one
+  * `:report_action` — return the CREATE action from the server's response
+    (`:created`, `:overwritten`, `:superseded`, or `:opened` for `:open_if`) instead of
+    `{:ok, bytes_written}` (default false)
+  * `:attributes` — `FILE_ATTRIBUTE_*` bits for a file this creates or replaces, e.g.
+    `0x2` (hidden) so a temp file is never visible, or `0x1` (read-only). Only
+    `READONLY`, `HIDDEN`, `SYSTEM`, `ARCHIVE`, `NORMAL`, `TEMPORARY`, `OFFLINE` and
+    `NOT_CONTENT_INDEXED` are allowed (`{:error, "bad_attributes"}` otherwise); default
+    `0`, the server's defaults. `:open_if` on an existing file leaves its attributes be
+  """
+  @spec write_file(String.t(), String.t(), String.t(), String.t(), iodata(), keyword()) ::
+          {:ok, non_neg_integer() | :created | :overwritten | :superseded | :opened}
+          | {:error,
+             :not_found
+             | :sharing_violation
+             | :exists
+             | :is_a_directory
+             | :no_space
+             | term()}
+  def write_file(url_or_unc, username, password, path, data, opts \\ []) do
+    sync = Keyword.get(opts, :sync, false)
+    disposition = Keyword.get(opts, :disposition, :overwrite)
+    msg = {:write_file, path, data, sync, disposition, Keyword.get(opts, :attributes, 0)}
+
+    result = with_sharing_retry(opts, fn -> call_pool(url_or_unc, username, password, msg) end)
+
+    case result do
+      {:ok, size, action} -> {:ok, if(opts[:report_action], do: action, else: size)}
+      other -> other
+    end
+  end
+
+  @doc """
+  Like `read_file/5`, but returns `{:error, {:too_large, size}}` instead of reading a
+  file bigger than `max_bytes`. The size is checked with a metadata query first.
+  """
+  @spec read_file_limited(String.t(), String.t(), String.t(), String.t(), non_neg_integer()) ::
+          {:ok, binary()} | {:error, {:too_large, non_neg_integer()} | term()}
+  def read_file_limited(url_or_unc, username, password, path, max_bytes),
+    do: call_pool(url_or_unc, username, password, {:read_file_limited, path, max_bytes})
+
+  @doc """
+  Like `read_file/5`, but also says what the content cost: `{:ok, bin, info}` with
+  `info` = `%{size:, allocation_size:, compressed_size:}`.
+
+  `allocation_size` below `size` means a sparse file; `compressed_size` is the on-disk
+  size of a compressed file as the server reports it (`nil` if it doesn't). The data
+  itself is not compressed on the wire by this client, so the transfer is `size` bytes.
+  Informational only.
+  """
+  @spec read_file_info(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, binary(), map()} | {:error, :not_found | term()}
+  def read_file_info(url_or_unc, username, password, path),
+    do: call_pool(url_or_unc, username, password, {:read_file_info, path})
+
+  @doc """
+  Stream a text file line by line to `pid` without loading it whole.
+
+  `pid` receives `{:line, bin}` for each line, in order, then `{:eof}`. Lines are split on
+  `"\\n"` only and are raw bytes: nothing is decoded, the `"\\n"` is stripped but a
+  `"\\r"` from CRLF files is kept, and a last line without a trailing newline is still
+  delivered. The call returns `:ok` after `{:eof}` has been sent; it blocks the pool
+  connection for the duration, so call it from a separate process for big files.
+
+  Pass `cancel: token` (from `cancel_token/0`) to be able to stop it with `cancel/1`;
+  it then returns `{:error, :cancelled}` without sending `{:eof}`. The same happens if
+  `pid` exits.
+  """
+  @spec stream_lines(String.t(), String.t(), String.t(), String.t(), pid(), keyword()) ::
+          :ok | {:error, :cancelled | :not_found | term()}
+  def stream_lines(url_or_unc, username, password, path, pid, opts \\ []) do
+    token = Keyword.get_lazy(opts, :cancel, &Operations.cancel_token/0)
+    call_pool(url_or_unc, username, password, {:stream_lines, path, pid, token})
+  end
+
+  @doc "A fresh cancellation token for `stream_lines/6` or `connect/4`'s `:cancel`."
+  @spec cancel_token() :: reference()
+  def cancel_token, do: Operations.cancel_token()
+
+  @doc """
+  Ask the `stream_lines/6` call using `token` to stop after its current chunk, or the
+  `connect/4` using it to give up.
+  """
+  @spec cancel(reference()) :: :ok
+  def cancel(token), do: Operations.cancel(token)
+
+  @doc """
+  Read several (small) files in one go, over one pooled connection and one lock
+  acquisition. Returns `{:ok, [{path, {:ok, binary} | {:error, reason}}]}` in input
+  order; a failing file doesn't fail the batch, e.g. a directory among the paths is
+  `{:error, :is_a_directory}` in its own entry. A file over the pool's
+  `:max_read_bytes` fails its own entry with `{:error, {:too_large, size}}`.
+  """
+  @spec read_files(String.t(), String.t(), String.t(), [String.t()]) ::
+          {:ok, [{String.t(), {:ok, binary()} | {:error, term()}}]} | {:error, term()}
+  def read_files(url_or_unc, username, password, paths),
+    do: call_pool(url_or_unc, username, password, {:read_files, paths})
+
+  @doc """
+  Set modification, access and creation times of many paths over one pooled connection
+  and one lock acquisition, e.g. to restore metadata after copying content.
+
+  `items` are `{path, mtime_ns, atime_ns, btime_ns}` in unix nanoseconds (as in the
+  `*_ns` fields of `get_file_stats/5`); `nil` leaves that time unchanged and
+  `:no_change` tells the server to stop updating it. Returns
+  `{:ok, [{path, :ok | {:error, reason}}]}` in input order; a failing path doesn't
+  stop the batch.
+  """
+  @spec set_times_many(String.t(), String.t(), String.t(), [
+          {String.t(), ns | nil | :no_change, ns | nil | :no_change, ns | nil | :no_change}
+        ]) :: {:ok, [{String.t(), :ok | {:error, term()}}]} | {:error, term()}
+        when ns: non_neg_integer()
+  def set_times_many(url_or_unc, username, password, items),
+    do: call_pool(url_or_unc, username, password, {:set_times_many, items})
+
+  @doc """
+  Set the `FILE_ATTRIBUTE_*` bitmask of many paths in one go: `items` are
+  `{path, attributes}`, results as for `set_times_many/4`. `0` leaves the attributes
+  unchanged; use `0x80` (NORMAL) to clear them all on a file.
+  """
+  @spec set_attributes_many(String.t(), String.t(), String.t(), [
+          {String.t(), non_neg_integer()}
+        ]) :: {:ok, [{String.t(), :ok | {:error, term()}}]} | {:error, term()}
+  def set_attributes_many(url_or_unc, username, password, items),
+    do: call_pool(url_or_unc, username, password, {:set_attributes_many, items})
+
+  @doc """
+  Like `write_file/5`, but readers never observe a half-written file: data goes to a
+  hidden temp file next to `path`, which is flushed and then renamed over `path`.
+  The temp file is cleaned up if anything fails.
+  """
+  @spec write_atomic(String.t(), String.t(), String.t(), String.t(), iodata()) ::
+          {:ok, non_neg_integer()} | {:error, :not_found | term()}
+  def write_atomic(url_or_unc, username, password, path, data),
+    do: call_pool(url_or_unc, username, password, {:write_atomic, path, data})
+
+  @doc """
+  Append `data` to `path` (created if missing), rotating it first when it would grow
+  past `max_size` bytes: the current file is renamed to `path <> ".1"`, replacing an
+  older rotation, and `data` starts a fresh file. Returns `{:ok, :appended}` or
+  `{:ok, :rotated}`.
+
+  Size check, rename and write happen under one lock of the pooled connection, so
+  appends through the same pool don't interleave; writers on other connections or
+  clients are not serialised. An empty file is never rotated.
+  """
+  @spec append_capped(String.t(), String.t(), String.t(), String.t(), iodata(), pos_integer()) ::
+          {:ok, :appended | :rotated} | {:error, term()}
+  def append_capped(url_or_unc, username, password, path, data, max_size)
+      when is_integer(max_size) and max_size > 0,
+      do: call_pool(url_or_unc, username, password, {:append_capped, path, data, max_size})
+
+  @doc """
+  Create `path` only if it doesn't exist and keep it open — a lock-file primitive.
+  Returns `{:ok, handle}`, or `{:error, :exists}` when another holder created it first.
+
+  With `delete_on_close: true` (default) the file is removed on `close/1`, which
+  releases the lock. The handle is also closed when it is garbage collected.
+  """
+  @spec create_exclusive(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          {:ok, reference()} | {:error, :exists | term()}
+  def create_exclusive(url_or_unc, username, password, path, opts \\ []) do
+    delete_on_close = Keyword.get(opts, :delete_on_close, true)
+    call_pool(url_or_unc, username, password, {:create_exclusive, path, delete_on_close})
+  end
+
+  @doc """
+  Open an existing `path` with an explicit desired-access mask and create options (raw
+  MS-SMB2 bit values), for least-privilege handles: e.g. `0x80` (`FILE_READ_ATTRIBUTES`)
+  or `0x10000` (`DELETE`) alone. Invalid combinations return `{:error, "bad_access"}` or
+  `{:error, "bad_options"}`. Release the handle with `close/1`.
+
+  Alongside the handle comes what the open found: `%{create_action: :opened,
+  end_of_file:, attributes:, granted_access:}`, so no follow-up stat is needed (size
+  and attributes are queried on the new handle, as the `smb` crate keeps neither them
+  nor the handle's FileId from the CREATE response). `granted_access` is the access the
+  handle actually carries, with `GENERIC_*` rights expanded to file rights; with
+  `MAXIMUM_ALLOWED` (`0x2000000`) it holds whatever the server allowed, which may be
+  less than you hoped for. Check it, e.g. for `FILE_WRITE_DATA` (`0x2`), before writing.
+
+  `opts` takes the `:hint` of `read_file/5` (`:sequential_scan` / `:random_access`)
+  and the `:no_recall` of `get_file_stats/5`, added to `options_bits`, and
+  `maximum_allowed: true`, which adds `MAXIMUM_ALLOWED` to `access_bits` (`0` is then
+  fine): the open gets the best access you are entitled to in one round-trip, and
+  `granted_access` says which.
+  """
+  @spec open(
+          String.t(),
+          String.t(),
+          String.t(),
+          String.t(),
+          non_neg_integer(),
+          non_neg_integer(),
+          keyword()
+        ) :: {:ok, reference(), map()} | {:error, :bad_hint | :not_found | term()}
+  def open(url_or_unc, username, password, path, access_bits, options_bits \\ 0, opts \\ []) do
+    with {:ok, hint} <- hint_bits(Keyword.get(opts, :hint)) do
+      no_recall = if opts[:no_recall], do: @file_open_no_recall, else: 0
+      options_bits = options_bits |> Bitwise.bor(hint) |> Bitwise.bor(no_recall)
+      max = if opts[:maximum_allowed], do: @maximum_allowed, else: 0
+      access_bits = Bitwise.bor(access_bits, max)
+      request = {:open, path, access_bits, options_bits}
+      call_pool(url_or_unc, username, password, request)
+    end
+  end
+
+  @doc """
+  Open `path` like `open/7`, but return what it is instead of the CREATE response:
+  `{:ok, handle, :file | :directory | :pipe}`. Leave both `FILE_DIRECTORY_FILE`
+  (`0x1`) and `FILE_NON_DIRECTORY_FILE` (`0x40`) out of `options_bits` and the one open
+  works for files and directories alike, so you can decide what to do with the handle
+  without opening the path again. Release it with `close/1`.
+  """
+  @spec open_resource(
+          String.t(),
+          String.t(),
+          String.t(),
+          String.t(),
+          non_neg_integer(),
+          non_neg_integer()
+        ) :: {:ok, reference(), :file | :directory | :pipe} | {:error, :not_found | term()}
+  def open_resource(url_or_unc, username, password, path, access_bits, options_bits \\ 0) do
+    request = {:open_resource, path, access_bits, options_bits}
+    call_pool(url_or_unc, username, password, request)
+  end
+
+  @doc """
+  Read up to `len` bytes at `offset` from an open handle.
+
+  Returns `{:ok, bin, :eof}` once the read reaches end-of-file: `offset + byte_size(bin)`
+  is at or past the file size, so a read ending exactly on the last byte is already
+  `:eof`, and a read starting at or past the end gives `{:ok, "", :eof}`. Otherwise
+  `{:ok, bin, :more}` — continue at `offset + byte_size(bin)`.
+  """
+  @spec pread(reference(), non_neg_integer(), non_neg_integer()) ::
+          {:ok, binary(), :eof | :more} | {:error, :closed | term()}
+  def pread(handle, offset, len), do: Operations.pread(handle, offset, len)
+
+  @doc """
+  Open a second handle to the same file as `handle` (same access and options), e.g. to
+  stream different ranges from two processes. `{:error, :closed}` if `handle` is closed.
+  """
+  @spec dup_handle(reference()) :: {:ok, reference()} | {:error, :closed | term()}
+  def dup_handle(handle), do: Operations.dup_handle(handle)
+
+  @doc """
+  Re-establish `handle` after its connection dropped or was reconnected (e.g. after an
+  idle logoff), so a long `handle_write/2` or `pread/3` sequence can resume: the file
+  is opened again with the same access and options and, for files, at the same
+  position. This is a plain reopen, not a durable-handle reclaim: locks, delete-on-close
+  and sharing state of the old open are lost, and the file may have changed meanwhile.
+  `{:error, :closed}` for a closed handle; if reopening fails the handle stays closed.
+  """
+  @spec reopen(reference()) :: :ok | {:error, :closed | :not_found | term()}
+  def reopen(handle), do: Operations.reopen(handle)
+
+  @doc """
+  Open the existing file `name` in the directory behind `dir_handle` (from `open/7`
+  with `FILE_DIRECTORY_FILE`), on that handle's connection, for `:read`, `:write` or
+  `:read_write`. Returns `{:ok, handle, info}` as `open/7` does, without going back
+  through the pool.
+
+  This is a path join, not a handle-relative open: SMB2 CREATE always names the file
+  from the share root, so the directory's path (as it was opened) and `name` are
+  joined and sent as one path. A directory renamed or replaced since `dir_handle` was
+  opened is not followed. `name` must be a single component: separators, `.` and `..`
+  give `{:error, "bad_path"}`. `{:error, :not_a_directory}` if `dir_handle` is a file.
+  """
+  @spec open_in_dir(reference(), String.t(), :read | :write | :read_write) ::
+          {:ok, reference(), map()}
+          | {:error, :not_a_directory | :closed | :not_found | term()}
+  def open_in_dir(dir_handle, name, mode \\ :read),
+    do: Operations.open_in_dir(dir_handle, name, mode)
+
+  @doc """
+  Open `path` for sequential writing and return `{:ok, handle, info}` (`info` as for
+  `open/6`). Write with `handle_write/2`, then `close/1`, which flushes first.
+
+  `:disposition` is one of `write_file/6`'s (`:overwrite` by default, `:create_new`,
+  `:open_if`, `:supersede`) or `:append`, which creates the file if needed and starts
+  writing at its current end. `:create_new` on an existing file is `{:error, :exists}`.
+  """
+  @spec open_writer(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          {:ok, reference(), map()} | {:error, :exists | :not_found | term()}
+  def open_writer(url_or_unc, username, password, path, opts \\ []) do
+    disposition = Keyword.get(opts, :disposition, :overwrite)
+    call_pool(url_or_unc, username, password, {:open_writer, path, disposition})
+  end
+
+  @doc """
+  Write `data` to a handle from `open_writer/5`, right after the previous write.
+  Returns `{:ok, bytes_written}`.
+  """
+  @spec handle_write(reference(), iodata()) ::
+          {:ok, non_neg_integer()} | {:error, :closed | term()}
+  def handle_write(handle, data),
+    do: Operations.handle_write(handle, IO.iodata_to_binary(data))
+
+  @doc """
+  Write `data` at `offset` through an open handle (from `open_writer/5`, or `open/7`
+  with write access), reusing it instead of opening the file again — for a long-lived
+  writer that places its records itself. Returns `{:ok, bytes_written}`; a following
+  `handle_write/2` continues right after it. Writes from several processes on the same
+  handle are serialized, each landing whole at its own offset.
+  """
+  @spec write_at_handle(reference(), non_neg_integer(), iodata()) ::
+          {:ok, non_neg_integer()} | {:error, :closed | :not_a_file | term()}
+  def write_at_handle(handle, offset, data) when is_integer(offset) and offset >= 0,
+    do: Operations.write_at_handle(handle, offset, IO.iodata_to_binary(data))
+
+  @doc """
+  Set the server-side current byte offset of `handle` (`FilePositionInformation`), for
+  servers that use it for relative operations. The next `handle_write/2` starts there
+  too; `pread/3` keeps its explicit offsets.
+  """
+  @spec set_position(reference(), non_neg_integer()) :: :ok | {:error, :closed | term()}
+  def set_position(handle, offset), do: Operations.set_position(handle, offset)
+
+  @doc "Flush a handle's written data to stable storage on the server (SMB2 FLUSH)."
+  @spec flush(reference()) :: :ok | {:error, :closed | term()}
+  def flush(handle), do: Operations.flush(handle)
+
+  @doc """
+  Close a handle returned by `create_exclusive/5`, `open/6` or `open_writer/5`
+  (idempotent). Handles opened for writing are flushed first; the handle is closed
+  even if that flush fails, and the flush error is returned.
+  """
+  @spec close(reference()) :: :ok | {:error, term()}
+  def close(handle), do: Operations.close(handle)
 
   @spec upload_file(String.t(), String.t(), String.t(), Path.t(), String.t()) ::
           {:ok, non_neg_integer()} | {:error, term()}
   def upload_file(url_or_unc, username, password, local_path, remote_path),
     do: call_pool(url_or_unc, username, password, {:upload_file, local_path, remote_path})
 
-  @spec download_file(String.t(), String.t(), String.t(), String.t(), Path.t()) ::
-          :ok | {:error, term()}
-  def download_file(url_or_unc, username, password, remote_path, local_path),
-    do: call_pool(url_or_unc, username, password, {:download_file, remote_path, local_path})
+  @doc """
+  Copy `remote_path` into the local file `local_path`, in 1 MiB reads.
+
+  Options:
+  * `:max_inflight` — how many reads are outstanding at once, each on its own handle
+    (default 4, max 64). Raise it on high-latency links, where one read at a time
+    leaves the line idle between round-trips
+
+  The local file is removed again if the download fails.
+  """
+  @spec download_file(String.t(), String.t(), String.t(), String.t(), Path.t(), keyword()) ::
+          :ok | {:error, :not_found | term()}
+  def download_file(url_or_unc, username, password, remote_path, local_path, opts \\ []) do
+    max_inflight = Keyword.get(opts, :max_inflight, 4)
+    msg = {:download_file, remote_path, local_path, max_inflight}
+    call_pool(url_or_unc, username, password, msg)
+  end
 
   @spec mkdir(String.t(), String.t(), String.t(), String.t()) :: :ok | {:error, term()}
   def mkdir(url_or_unc, username, password, path),
@@ -62,30 +703,364 @@ defmodule Rumbex do
   def mkdir_p(url_or_unc, username, password, path),
     do: call_pool(url_or_unc, username, password, {:mkdir_p, path})
 
-  @spec move_file(String.t(), String.t(), String.t(), String.t(), String.t()) ::
+  @doc """
+  Move/rename `from` to `to` within the share. Never replaces an existing `to`:
+  that fails with `{:error, :target_exists}`.
+
+  With `dry_run: true` nothing is renamed: the source is checked for existence and
+  `{:ok, [{from, to}]}` is returned (and logged) instead.
+  """
+  @spec move_file(String.t(), String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          :ok | {:ok, [{String.t(), String.t()}]} | {:error, :target_exists | term()}
+  def move_file(url_or_unc, username, password, from, to, opts \\ []) do
+    dry_run = Keyword.get(opts, :dry_run, false)
+
+    url_or_unc
+    |> call_pool(username, password, {:move_file, from, to, dry_run})
+    |> log_dry_run(dry_run, "move")
+  end
+
+  @doc """
+  Rename `old_name` to `new_name` within `dir`. Both are plain names; anything with a
+  `/` or `\\` returns `{:error, "bad_name"}`. Fails if `new_name` already exists.
+  """
+  @spec rename_in_dir(String.t(), String.t(), String.t(), String.t(), String.t(), String.t()) ::
           :ok | {:error, term()}
-  def move_file(url_or_unc, username, password, from, to),
-    do: call_pool(url_or_unc, username, password, {:move_file, from, to})
+  def rename_in_dir(url_or_unc, username, password, dir, old_name, new_name),
+    do: call_pool(url_or_unc, username, password, {:rename_in_dir, dir, old_name, new_name})
 
   @spec get_stat(String.t(), String.t(), String.t(), String.t()) ::
           {:ok, %{size: non_neg_integer(), type: :file | :directory}} | {:error, term()}
   def get_stat(url_or_unc, username, password, path),
     do: call_pool(url_or_unc, username, password, {:get_stat, path})
 
-  @spec get_file_stats(String.t(), String.t(), String.t(), String.t()) ::
-          {:ok, map()} | {:error, term()}
-  def get_file_stats(url_or_unc, username, password, path),
-    do: call_pool(url_or_unc, username, password, {:get_file_stats, path})
+  @doc """
+  Size, timestamps and attributes of `path`; takes the `:snapshot` option of `read_file/5`.
+
+  With `no_recall: true` the file is opened with `FILE_OPEN_NO_RECALL`, so an offline
+  file on an HSM / tiered share is not recalled from tape or cloud just to read its
+  metadata. Offline files have `FILE_ATTRIBUTE_OFFLINE` (`0x1000`) set in `:attributes`.
+  """
+  @spec get_file_stats(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          {:ok, map()} | {:error, :not_found | :bad_snapshot | term()}
+  def get_file_stats(url_or_unc, username, password, path, opts \\ []) do
+    options_bits = if opts[:no_recall], do: @file_open_no_recall, else: 0
+
+    with_snapshot(path, opts, fn path ->
+      call_pool(url_or_unc, username, password, {:get_file_stats, path, options_bits})
+    end)
+  end
+
+  @doc """
+  Attributes and timestamps only (`type`, `attributes`, `mtime`, `atime`, `ctime`,
+  `btime`), read through a `FILE_READ_ATTRIBUTES` open. Unlike `get_file_stats/4` this
+  succeeds on files another client holds open exclusively.
+  """
+  @spec stat_attrs(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, map()} | {:error, :not_found | term()}
+  def stat_attrs(url_or_unc, username, password, path),
+    do: call_pool(url_or_unc, username, password, {:stat_attrs, path})
+
+  @doc """
+  Whether `path` was last written after `unix_seconds`, in one attribute-only open:
+  the cheap "does this need re-copying" check. Compares whole seconds, like the
+  `:mtime` of `get_file_stats/5`, so passing back an `:mtime` read earlier gives
+  `false` for an unchanged file. `{:error, :not_found}` when nothing is at `path`.
+  """
+  @spec newer_than(String.t(), String.t(), String.t(), String.t(), integer()) ::
+          {:ok, boolean()} | {:error, :not_found | term()}
+  def newer_than(url_or_unc, username, password, path, unix_seconds)
+      when is_integer(unix_seconds),
+      do: call_pool(url_or_unc, username, password, {:newer_than, path, unix_seconds})
+
+  @doc """
+  Owner of `path` as a SID string (`"S-1-5-21-..."`), or `nil` if the security
+  descriptor has none. Only the owner is requested, through a `READ_CONTROL` open, so
+  it is much cheaper than reading the whole ACL. `{:error, :access_denied}` without
+  `READ_CONTROL` on the object.
+  """
+  @spec get_owner(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, String.t() | nil} | {:error, :access_denied | :not_found | term()}
+  def get_owner(url_or_unc, username, password, path),
+    do: call_pool(url_or_unc, username, password, {:get_owner, path})
+
+  @doc """
+  Makes `sid` (`"S-1-5-21-..."`) the owner of `path`; only the owner is written, the
+  DACL is untouched. Needs `WRITE_OWNER` on the object, and `SeRestorePrivilege` to
+  give it to anyone but yourself. `{:error, :bad_sid}` for a malformed SID string,
+  `{:error, :access_denied}` when the server refuses the open or the new owner.
+  """
+  @spec set_owner(String.t(), String.t(), String.t(), String.t(), String.t()) ::
+          :ok | {:error, :bad_sid | :access_denied | :not_found | term()}
+  def set_owner(url_or_unc, username, password, path, sid),
+    do: call_pool(url_or_unc, username, password, {:set_owner, path, sid})
+
+  @doc """
+  Breaks (`protected: true`) or re-enables (`false`) ACL inheritance on `path` by
+  setting or clearing `SE_DACL_PROTECTED`. The DACL's ACEs are written back unchanged:
+
+    * protecting keeps every ACE, including those inherited so far, so access does
+      not change until you edit the list; remove inherited ACEs afterwards if the
+      object should only carry its explicit ones
+    * unprotecting does not pull the parent's ACEs in by itself; they reappear when
+      the server next propagates inheritance from the parent
+
+  Needs `READ_CONTROL` and `WRITE_DAC`; `{:error, :access_denied}` otherwise.
+  """
+  @spec set_dacl_protected(String.t(), String.t(), String.t(), String.t(), boolean()) ::
+          :ok | {:error, :access_denied | :not_found | term()}
+  def set_dacl_protected(url_or_unc, username, password, path, protected)
+      when is_boolean(protected),
+      do: call_pool(url_or_unc, username, password, {:set_dacl_protected, path, protected})
+
+  @doc """
+  Whether `path` is a junction / mount point (reparse tag `IO_REPARSE_TAG_MOUNT_POINT`).
+  Symlinks are not junctions. Recursive walkers should not descend into these, as
+  they may point back into the tree.
+  """
+  @spec is_junction(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, boolean()} | {:error, :not_found | term()}
+  def is_junction(url_or_unc, username, password, path),
+    do: call_pool(url_or_unc, username, password, {:is_junction, path})
+
+  @doc """
+  The server's canonical form of `path`: case as stored on disk and 8.3 short names
+  expanded (`"progra~1/app.ini"` -> `"Program Files/App.ini"`), `/`-separated and
+  relative to the share. Servers older than SMB 3.1.1 may leave short names as given.
+  """
+  @spec real_path(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, String.t()} | {:error, :not_found | term()}
+  def real_path(url_or_unc, username, password, path),
+    do: call_pool(url_or_unc, username, password, {:real_path, path})
+
+  @doc """
+  Low-level escape hatch: send a raw SMB2 SET_INFO for file-information class
+  `info_class` (the MS-FSCC number, e.g. `14` for FilePositionInformation) with
+  `buffer` as the class's little-endian wire encoding.
+
+  Supported classes: 4 (Basic), 14 (Position), 16 (Mode), 19 (Allocation),
+  20 (EndOfFile) and 39 (ValidDataLength); others give `{:error, :unsupported}`.
+  Nothing checks that the values make sense — a well-formed but wrong buffer (say, an
+  EndOfFile of 0) is applied as is, so prefer the dedicated functions where they exist.
+  A buffer that does not parse as the class gives `{:error, "bad_buffer"}`; a server
+  rejection gives `{:error, {:ntstatus, code}}`, except a full disk: `{:error, :no_space}`.
+  """
+  @spec set_info(String.t(), String.t(), String.t(), String.t(), non_neg_integer(), binary()) ::
+          :ok
+          | {:error, :unsupported | :not_found | :no_space | {:ntstatus, non_neg_integer()}}
+          | {:error, term()}
+  def set_info(url_or_unc, username, password, path, info_class, buffer),
+    do: call_pool(url_or_unc, username, password, {:set_info, path, info_class, buffer})
+
+  @doc """
+  Low-level counterpart of `set_info/6`: `{:ok, bin}` with file-information class
+  `info_class` of `path` in its MS-FSCC little-endian layout, to be parsed by the caller.
+
+  Supported classes: 4 (Basic), 5 (Standard), 6 (Internal), 7 (Ea), 8 (Access),
+  14 (Position), 16 (Mode), 17 (Alignment), 28 (Compression), 34 (NetworkOpen),
+  35 (AttributeTag), 48 (NormalizedName), 59 (Id). Any other class, or one the server
+  rejects, gives `{:error, :unsupported}`.
+  """
+  @spec query_info_raw(String.t(), String.t(), String.t(), String.t(), non_neg_integer()) ::
+          {:ok, binary()} | {:error, :unsupported | :not_found | term()}
+  def query_info_raw(url_or_unc, username, password, path, info_class),
+    do: call_pool(url_or_unc, username, password, {:query_info_raw, path, info_class})
 
+  @doc """
+  Labels of the shadow copies (previous versions) available for `path`, e.g.
+  `"@GMT-2024.05.01-12.00.00"`. `{:error, :unsupported}` if the server does not
+  expose snapshots. Use `snapshot_path/2` to read a file as of one of them.
+  """
+  @spec list_snapshots(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, [String.t()]} | {:error, :unsupported | :not_found | term()}
+  def list_snapshots(url_or_unc, username, password, path),
+    do: call_pool(url_or_unc, username, password, {:list_snapshots, path})
+
+  @doc """
+  Path of `path` inside the snapshot `label` (as returned by `list_snapshots/4`).
+  Pass it to `read_file/4`, `get_file_stats/4`, `list_dir/4`, ... to access the file as
+  it was at that time; snapshots are read-only. Backslashes in `path` become `/`.
+
+      iex> Rumbex.snapshot_path("/dir/a.txt", "@GMT-2024.05.01-12.00.00")
+      "@GMT-2024.05.01-12.00.00/dir/a.txt"
+  """
+  @spec snapshot_path(String.t(), String.t()) :: String.t()
+  def snapshot_path(path, "@GMT-" <> _ = label),
+    do: label <> "/" <> Path.norm(String.replace(path, "\\", "/"))
+
+  @doc """
+  Kind of the object at `path`; `{:error, :not_found}` when there is nothing there.
+  Symlinks are reported as `:symlink` without being followed. The share root (`""`,
+  `"/"` or `"\\"`) is opened on the server and its answer classified by the same rules
+  as any other path, as `get_stat/4` and `get_file_stats/5` do.
+  """
   @spec exists(String.t(), String.t(), String.t(), String.t()) ::
-          {:ok, :file | :directory | :not_found} | {:error, term()}
+          {:ok, :file | :directory | :symlink} | {:error, :not_found | term()}
   def exists(url_or_unc, username, password, path),
     do: call_pool(url_or_unc, username, password, {:exists, path})
 
-  @doc "Not working as exected for now, it does not really delete file, but marks it for deletion"
-  @spec delete_file(String.t(), String.t(), String.t(), String.t()) :: :ok | {:error, term()}
-  def delete_file(url_or_unc, username, password, path),
-    do: call_pool(url_or_unc, username, password, {:delete_file, path})
+  @doc "Boolean form of `exists/4`; any error counts as absent."
+  @spec exists?(String.t(), String.t(), String.t(), String.t()) :: boolean()
+  def exists?(url_or_unc, username, password, path),
+    do: match?({:ok, _}, exists(url_or_unc, username, password, path))
+
+  @doc """
+  `exists/4` for many paths in one pool call: `{:ok, [{path, kind}]}` in the order
+  given, `kind` being `:file`, `:directory`, `:symlink` or `:not_found`. One
+  attributes-only open per path on a single pooled connection, without taking the
+  connection's lock per path, so hundreds of checks cost far less than separate calls.
+  """
+  @spec exists_many(String.t(), String.t(), String.t(), [String.t()]) ::
+          {:ok, [{String.t(), :file | :directory | :symlink | :not_found}]}
+          | {:error, term()}
+  def exists_many(url_or_unc, username, password, paths) when is_list(paths),
+    do: call_pool(url_or_unc, username, password, {:exists_many, paths})
+
+  @doc """
+  Negotiated server properties: GUID, dialect, capability flags (`:dfs`, `:leasing`,
+  `:large_mtu`, `:multichannel`, `:persistent_handles`, `:directory_leasing`,
+  `:encryption`) and max read/write/transact sizes. They come from a NEGOTIATE of
+  their own with the pool's server, offering the same dialects as the pool does.
+  `:max_credits` is the credit window the pool's connection asks for (see
+  `:max_credits` of `connect/4`); the server may grant fewer, which the smb client
+  doesn't report.
+  """
+  @spec server_info(String.t(), String.t(), String.t()) :: {:ok, map()} | {:error, term()}
+  def server_info(url_or_unc, username, password),
+    do: call_pool(url_or_unc, username, password, :server_info)
+
+  @doc """
+  The full UNC an operation on `path` would use, e.g. `{:ok, "\\\\host\\share\\dir\\f.txt"}`,
+  built exactly as the other functions build it but without any I/O — for logging, or
+  to check a path before something destructive like `rm_rf/5`. A `..` segment is
+  refused with `{:error, "bad_segment: '..'"}`.
+  """
+  @spec resolve(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, String.t()} | {:error, term()}
+  def resolve(url_or_unc, username, password, path),
+    do: call_pool(url_or_unc, username, password, {:resolve, path})
+
+  @doc """
+  Whether the share refuses writes, to fail fast before an upload. Nothing is written:
+  the share root is opened with `MAXIMUM_ALLOWED`, and the share is read-only when the
+  access the server reports there includes neither creating files nor subdirectories
+  (share permissions and the root's ACL both count). Once any request on the pooled
+  connection has failed with `STATUS_MEDIA_WRITE_PROTECTED` (a write-protected volume
+  the permissions don't reveal), the answer is `true` without asking the server.
+  """
+  @spec share_readonly(String.t(), String.t(), String.t()) ::
+          {:ok, boolean()} | {:error, term()}
+  def share_readonly(url_or_unc, username, password),
+    do: call_pool(url_or_unc, username, password, :share_readonly)
+
+  @doc """
+  Like `list_dir/4`, with the server's file id of every entry. Ids survive renames
+  and can be used with `open_by_id/4`, `read_file_by_id/4` and `file_stats_by_id/4`.
+  If enumeration breaks off, the entries read so far come back as
+  `{:partial, entries, reason}`.
+  """
+  @spec list_dir_ids(String.t(), String.t(), String.t(), String.t()) ::
+          {:ok, [{String.t(), :file | :directory, non_neg_integer()}]}
+          | {:partial, [{String.t(), :file | :directory, non_neg_integer()}], String.t()}
+          | {:error, term()}
+  def list_dir_ids(url_or_unc, username, password, path \\ "/"),
+    do: call_pool(url_or_unc, username, password, {:list_dir_ids, path})
+
+  @doc """
+  Open a file by its id (FILE_OPEN_BY_FILE_ID). Returns `{:error, :unsupported}` when
+  the server or share doesn't support opening by id.
+  """
+  @spec open_by_id(String.t(), String.t(), String.t(), non_neg_integer()) ::
+          {:ok, reference()} | {:error, :unsupported | :not_found | term()}
+  def open_by_id(url_or_unc, username, password, file_id),
+    do: call_pool(url_or_unc, username, password, {:open_by_id, file_id})
+
+  @doc """
+  Read a whole file by its id (see `list_dir_ids/4`). Subject to the connection's
+  `:max_read_bytes` like `read_file/5`: a bigger file is `{:error, {:too_large, size}}`.
+  """
+  @spec read_file_by_id(String.t(), String.t(), String.t(), non_neg_integer()) ::
+          {:ok, binary()}
+          | {:error, :unsupported | :not_found | {:too_large, non_neg_integer()} | term()}
+  def read_file_by_id(url_or_unc, username, password, file_id),
+    do: call_pool(url_or_unc, username, password, {:read_file_by_id, file_id})
+
+  @spec file_stats_by_id(String.t(), String.t(), String.t(), non_neg_integer()) ::
+          {:ok, map()} | {:error, :unsupported | :not_found | term()}
+  def file_stats_by_id(url_or_unc, username, password, file_id),
+    do: call_pool(url_or_unc, username, password, {:file_stats_by_id, file_id})
+
+  @doc """
+  Delete a file or empty directory.
+
+  Deletion is deferred: the file is marked delete-on-close and the server removes it
+  once every open handle on it is closed, so by default `:ok` may come back while the
+  entry is still visible (an immediate `exists?/4` can still see it). Pass
+  `wait: true` to re-check until the name is actually gone; if other handles keep it
+  pending for more than about a second this returns `{:error, :delete_pending}`.
+
+  With `dry_run: true` nothing is deleted: returns (and logs) `{:ok, [path]}` if the
+  path exists, `{:ok, []}` if it doesn't.
+  """
+  @spec delete_file(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          :ok | {:ok, [String.t()]} | {:error, :delete_pending | term()}
+  def delete_file(url_or_unc, username, password, path, opts \\ []) do
+    dry_run = Keyword.get(opts, :dry_run, false)
+    wait = Keyword.get(opts, :wait, false)
+
+    url_or_unc
+    |> call_pool(username, password, {:delete_file, path, dry_run, wait})
+    |> log_dry_run(dry_run, "delete")
+  end
+
+  @doc """
+  Recursively delete `path` (a file or a whole directory tree).
+
+  Each directory is opened once with `FILE_DELETE_CHILD` and its entries deleted under
+  that right; where the server refuses it, every entry gets its own delete-on-close
+  open instead. The result is the same either way.
+
+  Options:
+    * `:follow_symlinks` (default `false`) — when `false`, symlinks and junctions inside
+      the tree are deleted as links and their targets are left alone, so nothing outside
+      `path` is touched. When `true`, junctions / mount points are descended into and
+      everything under their target is deleted as well, each directory at most once: a
+      junction leading back to a directory already walked is removed as a link. SMB
+      symlinks are resolved by the client, not the server, so they are always removed
+      as links.
+    * `:dry_run` — delete nothing; returns (and logs) `{:ok, paths}` with everything that
+      would be deleted, deepest first.
+    * `:skip_offline` (default `false`) — leave files with `FILE_ATTRIBUTE_OFFLINE`
+      (tiered away on an HSM share) and the directories holding them in place. The
+      offline paths are returned as an extra element: `{:ok, offline}`, or
+      `{:ok, paths, offline}` with `:dry_run`.
+  """
+  @spec rm_rf(String.t(), String.t(), String.t(), String.t(), keyword()) ::
+          :ok
+          | {:ok, [String.t()]}
+          | {:ok, [String.t()], [String.t()]}
+          | {:error, term()}
+  def rm_rf(url_or_unc, username, password, path, opts \\ []) do
+    follow = Keyword.get(opts, :follow_symlinks, false)
+    dry_run = Keyword.get(opts, :dry_run, false)
+    skip_offline = Keyword.get(opts, :skip_offline, false)
+
+    url_or_unc
+    |> call_pool(username, password, {:rm_rf, path, follow, dry_run, skip_offline})
+    |> log_dry_run(dry_run, "delete")
+  end
+
+  @doc """
+  Change the per-request timeout (`:timeout_ms` of `connect/4`) of every connection in
+  the pool; `0` turns it off. A request the server doesn't answer in time fails, so a
+  hung server can't pin a dirty scheduler thread. The connections are re-established
+  to apply it (waiting for operations in flight); handles opened earlier through
+  `open/6` or `create_exclusive/5` keep the previous setting.
+  """
+  @spec set_timeout(String.t(), String.t(), String.t(), non_neg_integer()) ::
+          :ok | {:error, term()}
+  def set_timeout(url_or_unc, username, password, ms) when is_integer(ms) and ms >= 0,
+    do: call_pool(url_or_unc, username, password, {:set_timeout, ms})
 
   @doc "Stop and remove the pool for the combination (url_or_unc, username, password)."
   @spec stop_pool(String.t(), String.t(), String.t()) :: :ok | {:error, term()}
@@ -101,15 +1076,66 @@ defmodule Rumbex do
 
   # ───────── Internal: ensure + pool call ─────────
 
+  defp with_snapshot(path, opts, fun) do
+    case Keyword.get(opts, :snapshot) do
+      nil ->
+        fun.(path)
+
+      label when is_binary(label) ->
+        if Regex.match?(@snapshot_token, label),
+          do: fun.(snapshot_path(path, label)),
+          else: {:error, :bad_snapshot}
+
+      _ ->
+        {:error, :bad_snapshot}
+    end
+  end
+
+  defp log_dry_run({:ok, targets} = result, true, what) do
+    Logger.info("[Rumbex] dry run, would #{what}: #{inspect(targets)}")
+    result
+  end
+
+  defp log_dry_run({:ok, targets, _offline} = result, true, what) do
+    Logger.info("[Rumbex] dry run, would #{what}: #{inspect(targets)}")
+    result
+  end
+
+  defp log_dry_run(result, _dry_run, _what), do: result
+
+  # Read-ahead hints as CreateOptions bits: FILE_SEQUENTIAL_ONLY / FILE_RANDOM_ACCESS
+  defp hint_bits(nil), do: {:ok, 0}
+  defp hint_bits(:sequential_scan), do: {:ok, 0x4}
+  defp hint_bits(:random_access), do: {:ok, 0x800}
+  defp hint_bits(_), do: {:error, :bad_hint}
+
+  # Opt-in, bounded retry for transient sharing violations. Sleeps in the caller,
+  # not in the pool, so other users of the pool aren't blocked meanwhile.
+  defp with_sharing_retry(opts, fun) do
+    retries = opts |> Keyword.get(:sharing_violation_retries, 0) |> min(10)
+    retry_sharing(fun, retries, Keyword.get(opts, :retry_backoff, 100))
+  end
+
+  defp retry_sharing(fun, retries, backoff) do
+    case fun.() do
+      {:error, :sharing_violation} when retries > 0 ->
+        Process.sleep(backoff)
+        retry_sharing(fun, retries - 1, min(backoff * 2, 5_000))
+
+      other ->
+        other
+    end
+  end
+
   defp call_pool(url_or_unc, username, password, msg) do
     with {:ok, name} <- ensure_pool(url_or_unc, username, password) do
       GenServer.call(via(name), msg, :infinity)
     end
   end
 
-  @spec ensure_pool(String.t(), String.t(), String.t(), pos_integer()) ::
+  @spec ensure_pool(String.t(), String.t(), String.t(), pos_integer(), map()) ::
           {:ok, atom()} | {:error, term()}
-  defp ensure_pool(url_or_unc, username, password, size \\ 5) do
+  defp ensure_pool(url_or_unc, username, password, size \\ 5, conn_opts \\ %{}) do
     {unc, _rel} = Path.parse_smb_url!(url_or_unc)
     name = pool_name(unc, username, password)
 
@@ -122,7 +1148,16 @@ defmodule Rumbex do
           id: name,
           start:
             {Pool, :start_link,
-             [[name: via(name), url: unc, username: username, password: password, size: size]]}
+             [
+               [
+                 name: via(name),
+                 url: unc,
+                 username: username,
+                 password: password,
+                 size: size,
+                 conn_opts: conn_opts
+               ]
+             ]}
         }
 
         case DynamicSupervisor.start_child(PoolSupervisor, spec) do
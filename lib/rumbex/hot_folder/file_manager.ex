@@ -4,6 +4,8 @@ defmodule Rumbex.HotFolder.FileManager do
   """
   alias Rumbex
 
+  require Logger
+
   def ensure_layout!(url, u, p, base, folders) do
     Enum.each(folders, fn {_k, rel} ->
       full = join(base, rel)
@@ -16,11 +18,28 @@ defmodule Rumbex.HotFolder.FileManager do
   @spec list_files(String.t(), String.t(), String.t(), String.t()) ::
           {:ok, list()} | {:error, term()}
   def list_files(url, u, p, dir) do
-    with {:ok, entries} <- Rumbex.list_dir(url, u, p, dir) do
-      {:ok, Enum.reject(entries, &dir?/1)}
+    case Rumbex.list_dir_counted(url, u, p, dir) do
+      {:ok, entries, skipped} ->
+        files(entries, skipped, dir)
+
+      # the rest of the directory is picked up on the next poll
+      {:partial, entries, skipped, reason} ->
+        Logger.warning("[Rumbex.HotFolder] listing of #{dir} truncated: #{reason}")
+        files(entries, skipped, dir)
+
+      error ->
+        error
     end
   end
 
+  defp files(entries, skipped, dir) do
+    if skipped > 0 do
+      Logger.warning("[Rumbex.HotFolder] #{skipped} corrupted record(s) skipped in #{dir}")
+    end
+
+    {:ok, Enum.reject(entries, &dir?/1)}
+  end
+
   @doc """
   Extract a basic file tuple {name, size} from entry returned by `Rumbex.list_dir/4`.
   We support: binaries, and maps with keys like :name/"name"/:filename and :type/:size.
@@ -116,7 +135,7 @@ defmodule Rumbex.HotFolder.FileManager do
 
   def delete_if_exists(url, u, p, path) do
     case Rumbex.exists(url, u, p, path) do
-      {:ok, :file} -> Rumbex.delete_file(url, u, p, path)
+      {:ok, kind} when kind in [:file, :symlink] -> Rumbex.delete_file(url, u, p, path)
       {:ok, :directory} -> {:error, :target_is_directory}
       _ -> :ok
     end
@@ -11,17 +11,102 @@ if Mix.target() == :rpi5 do
 
     def connect(_unc, _user, _pass), do: :erlang.nif_error(:nif_not_loaded)
     def read_file(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
-    def write_file(_conn, _path, _data), do: :erlang.nif_error(:nif_not_loaded)
+
+    def write_file(_conn, _path, _data, _sync, _disposition, _attributes),
+      do: :erlang.nif_error(:nif_not_loaded)
+
     def list_dir(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def stat(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def mkdir_p(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def mkdir(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def exists(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
-    def rm(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def rm(_conn, _path, _dry_run, _wait), do: :erlang.nif_error(:nif_not_loaded)
     def file_stats(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
 
-    def rename(_conn, _old_path, _new_path, _replace_if_exists),
+    def rename(_conn, _old_path, _new_path, _replace_if_exists, _dry_run),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def list_dir_counted(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def authenticate(_server, _user, _pass), do: :erlang.nif_error(:nif_not_loaded)
+    def list_shares(_session), do: :erlang.nif_error(:nif_not_loaded)
+    def tree_connect(_session, _share), do: :erlang.nif_error(:nif_not_loaded)
+    def server_info(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def connect_with_opts(_unc, _user, _pass, _opts), do: :erlang.nif_error(:nif_not_loaded)
+    def idle_state(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def write_atomic(_conn, _path, _data), do: :erlang.nif_error(:nif_not_loaded)
+    def create_exclusive(_conn, _path, _delete_on_close), do: :erlang.nif_error(:nif_not_loaded)
+    def close(_handle), do: :erlang.nif_error(:nif_not_loaded)
+    def list_dir_ids(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def open_by_id(_conn, _file_id), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_by_id(_conn, _file_id), do: :erlang.nif_error(:nif_not_loaded)
+    def file_stats_by_id(_conn, _file_id), do: :erlang.nif_error(:nif_not_loaded)
+    def open(_conn, _path, _access_bits, _options_bits), do: :erlang.nif_error(:nif_not_loaded)
+    def stat_attrs(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def last_status(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def connect_with_hash(_unc, _auth, _opts), do: :erlang.nif_error(:nif_not_loaded)
+    def connect_kerberos(_unc, _principal, _pass, _opts), do: :erlang.nif_error(:nif_not_loaded)
+    def io_stats(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def reset_io_stats(_conn), do: :erlang.nif_error(:nif_not_loaded)
+
+    def rename_in_dir(_conn, _dir_path, _old_name, _new_name),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def is_junction(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def dup_handle(_handle), do: :erlang.nif_error(:nif_not_loaded)
+    def pread(_handle, _offset, _len), do: :erlang.nif_error(:nif_not_loaded)
+    def read_files(_conn, _paths), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_limited(_conn, _path, _max_bytes), do: :erlang.nif_error(:nif_not_loaded)
+    def clear_handle_cache(_conn), do: :erlang.nif_error(:nif_not_loaded)
+
+    def rm_rf(_conn, _path, _follow_symlinks, _dry_run, _skip_offline),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def list_snapshots(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def cancel_token, do: :erlang.nif_error(:nif_not_loaded)
+    def cancel(_token), do: :erlang.nif_error(:nif_not_loaded)
+    def stream_lines(_conn, _path, _pid, _cancel), do: :erlang.nif_error(:nif_not_loaded)
+    def set_info(_conn, _path, _info_class, _buffer), do: :erlang.nif_error(:nif_not_loaded)
+    def query_info_raw(_conn, _path, _info_class), do: :erlang.nif_error(:nif_not_loaded)
+    def real_path(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def list_dir_both_names(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def set_timeout(_conn, _ms), do: :erlang.nif_error(:nif_not_loaded)
+
+    def walk_parallel(_conn, _root, _pid, _workers, _skip_offline),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def set_times_many(_conn, _items), do: :erlang.nif_error(:nif_not_loaded)
+    def set_attributes_many(_conn, _items), do: :erlang.nif_error(:nif_not_loaded)
+    def open_writer(_conn, _path, _disposition), do: :erlang.nif_error(:nif_not_loaded)
+    def handle_write(_handle, _data), do: :erlang.nif_error(:nif_not_loaded)
+    def flush(_handle), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_info(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_with_options(_conn, _path, _options), do: :erlang.nif_error(:nif_not_loaded)
+    def probe(_host, _port), do: :erlang.nif_error(:nif_not_loaded)
+    def set_position(_handle, _offset), do: :erlang.nif_error(:nif_not_loaded)
+    def count_entries(_conn, _path, _pattern), do: :erlang.nif_error(:nif_not_loaded)
+    def file_stats_with_options(_conn, _path, _options), do: :erlang.nif_error(:nif_not_loaded)
+
+    def download_file(_conn, _path, _local_path, _max_inflight),
       do: :erlang.nif_error(:nif_not_loaded)
+
+    def get_owner(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def set_owner(_conn, _path, _sid), do: :erlang.nif_error(:nif_not_loaded)
+    def set_dacl_protected(_conn, _path, _protected), do: :erlang.nif_error(:nif_not_loaded)
+    def list_dir_class(_conn, _path, _class), do: :erlang.nif_error(:nif_not_loaded)
+    def list_names(_conn, _path, _pattern), do: :erlang.nif_error(:nif_not_loaded)
+    def share_readonly(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def open_in_dir(_dir_handle, _name, _mode), do: :erlang.nif_error(:nif_not_loaded)
+    def newer_than(_conn, _path, _unix_seconds), do: :erlang.nif_error(:nif_not_loaded)
+    def exists_many(_conn, _paths), do: :erlang.nif_error(:nif_not_loaded)
+    def reopen(_handle), do: :erlang.nif_error(:nif_not_loaded)
+
+    def append_capped(_conn, _path, _data, _max_size), do: :erlang.nif_error(:nif_not_loaded)
+
+    def open_resource(_conn, _path, _access_bits, _options_bits),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def resolve(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def write_at_handle(_handle, _offset, _data), do: :erlang.nif_error(:nif_not_loaded)
   end
 else
   defmodule Rumbex.Native do
@@ -29,16 +114,101 @@ else
 
     def connect(_unc, _user, _pass), do: :erlang.nif_error(:nif_not_loaded)
     def read_file(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
-    def write_file(_conn, _path, _data), do: :erlang.nif_error(:nif_not_loaded)
+
+    def write_file(_conn, _path, _data, _sync, _disposition, _attributes),
+      do: :erlang.nif_error(:nif_not_loaded)
+
     def list_dir(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def stat(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def mkdir_p(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def mkdir(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
     def exists(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
-    def rm(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def rm(_conn, _path, _dry_run, _wait), do: :erlang.nif_error(:nif_not_loaded)
     def file_stats(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
 
-    def rename(_conn, _old_path, _new_path, _replace_if_exists),
+    def rename(_conn, _old_path, _new_path, _replace_if_exists, _dry_run),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def list_dir_counted(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def authenticate(_server, _user, _pass), do: :erlang.nif_error(:nif_not_loaded)
+    def list_shares(_session), do: :erlang.nif_error(:nif_not_loaded)
+    def tree_connect(_session, _share), do: :erlang.nif_error(:nif_not_loaded)
+    def server_info(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def connect_with_opts(_unc, _user, _pass, _opts), do: :erlang.nif_error(:nif_not_loaded)
+    def idle_state(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def write_atomic(_conn, _path, _data), do: :erlang.nif_error(:nif_not_loaded)
+    def create_exclusive(_conn, _path, _delete_on_close), do: :erlang.nif_error(:nif_not_loaded)
+    def close(_handle), do: :erlang.nif_error(:nif_not_loaded)
+    def list_dir_ids(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def open_by_id(_conn, _file_id), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_by_id(_conn, _file_id), do: :erlang.nif_error(:nif_not_loaded)
+    def file_stats_by_id(_conn, _file_id), do: :erlang.nif_error(:nif_not_loaded)
+    def open(_conn, _path, _access_bits, _options_bits), do: :erlang.nif_error(:nif_not_loaded)
+    def stat_attrs(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def last_status(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def connect_with_hash(_unc, _auth, _opts), do: :erlang.nif_error(:nif_not_loaded)
+    def connect_kerberos(_unc, _principal, _pass, _opts), do: :erlang.nif_error(:nif_not_loaded)
+    def io_stats(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def reset_io_stats(_conn), do: :erlang.nif_error(:nif_not_loaded)
+
+    def rename_in_dir(_conn, _dir_path, _old_name, _new_name),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def is_junction(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def dup_handle(_handle), do: :erlang.nif_error(:nif_not_loaded)
+    def pread(_handle, _offset, _len), do: :erlang.nif_error(:nif_not_loaded)
+    def read_files(_conn, _paths), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_limited(_conn, _path, _max_bytes), do: :erlang.nif_error(:nif_not_loaded)
+    def clear_handle_cache(_conn), do: :erlang.nif_error(:nif_not_loaded)
+
+    def rm_rf(_conn, _path, _follow_symlinks, _dry_run, _skip_offline),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def list_snapshots(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def cancel_token, do: :erlang.nif_error(:nif_not_loaded)
+    def cancel(_token), do: :erlang.nif_error(:nif_not_loaded)
+    def stream_lines(_conn, _path, _pid, _cancel), do: :erlang.nif_error(:nif_not_loaded)
+    def set_info(_conn, _path, _info_class, _buffer), do: :erlang.nif_error(:nif_not_loaded)
+    def query_info_raw(_conn, _path, _info_class), do: :erlang.nif_error(:nif_not_loaded)
+    def real_path(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def list_dir_both_names(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def set_timeout(_conn, _ms), do: :erlang.nif_error(:nif_not_loaded)
+
+    def walk_parallel(_conn, _root, _pid, _workers, _skip_offline),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def set_times_many(_conn, _items), do: :erlang.nif_error(:nif_not_loaded)
+    def set_attributes_many(_conn, _items), do: :erlang.nif_error(:nif_not_loaded)
+    def open_writer(_conn, _path, _disposition), do: :erlang.nif_error(:nif_not_loaded)
+    def handle_write(_handle, _data), do: :erlang.nif_error(:nif_not_loaded)
+    def flush(_handle), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_info(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def read_file_with_options(_conn, _path, _options), do: :erlang.nif_error(:nif_not_loaded)
+    def probe(_host, _port), do: :erlang.nif_error(:nif_not_loaded)
+    def set_position(_handle, _offset), do: :erlang.nif_error(:nif_not_loaded)
+    def count_entries(_conn, _path, _pattern), do: :erlang.nif_error(:nif_not_loaded)
+    def file_stats_with_options(_conn, _path, _options), do: :erlang.nif_error(:nif_not_loaded)
+
+    def download_file(_conn, _path, _local_path, _max_inflight),
       do: :erlang.nif_error(:nif_not_loaded)
+
+    def get_owner(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def set_owner(_conn, _path, _sid), do: :erlang.nif_error(:nif_not_loaded)
+    def set_dacl_protected(_conn, _path, _protected), do: :erlang.nif_error(:nif_not_loaded)
+    def list_dir_class(_conn, _path, _class), do: :erlang.nif_error(:nif_not_loaded)
+    def list_names(_conn, _path, _pattern), do: :erlang.nif_error(:nif_not_loaded)
+    def share_readonly(_conn), do: :erlang.nif_error(:nif_not_loaded)
+    def open_in_dir(_dir_handle, _name, _mode), do: :erlang.nif_error(:nif_not_loaded)
+    def newer_than(_conn, _path, _unix_seconds), do: :erlang.nif_error(:nif_not_loaded)
+    def exists_many(_conn, _paths), do: :erlang.nif_error(:nif_not_loaded)
+    def reopen(_handle), do: :erlang.nif_error(:nif_not_loaded)
+
+    def append_capped(_conn, _path, _data, _max_size), do: :erlang.nif_error(:nif_not_loaded)
+
+    def open_resource(_conn, _path, _access_bits, _options_bits),
+      do: :erlang.nif_error(:nif_not_loaded)
+
+    def resolve(_conn, _path), do: :erlang.nif_error(:nif_not_loaded)
+    def write_at_handle(_handle, _offset, _data), do: :erlang.nif_error(:nif_not_loaded)
   end
 end
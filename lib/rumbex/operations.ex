@@ -2,6 +2,118 @@ defmodule Rumbex.Operations do
   @moduledoc false
   alias Rumbex.Native
 
+  def connect_with_hash(unc, auth, opts) do
+    try do
+      Native.connect_with_hash(unc, auth, opts)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def connect_kerberos(unc, principal, password, opts) do
+    try do
+      Native.connect_kerberos(unc, principal, password, opts)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def authenticate(server, user, pass) do
+    try do
+      Native.authenticate(server, user, pass)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def probe(host, port) do
+    try do
+      Native.probe(host, port)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def list_shares(session) do
+    try do
+      Native.list_shares(session)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def tree_connect(session, share) do
+    try do
+      Native.tree_connect(session, share)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def server_info(conn) do
+    try do
+      Native.server_info(conn)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def share_readonly(conn) do
+    try do
+      Native.share_readonly(conn)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def resolve(conn, rel) do
+    try do
+      Native.resolve(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def idle_state(conn) do
+    try do
+      Native.idle_state(conn)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def last_status(conn) do
+    try do
+      Native.last_status(conn)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def io_stats(conn) do
+    try do
+      Native.io_stats(conn)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def reset_io_stats(conn) do
+    try do
+      Native.reset_io_stats(conn)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def clear_handle_cache(conn) do
+    try do
+      Native.clear_handle_cache(conn)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
   def list_dir(conn, rel) do
     try do
       Native.list_dir(conn, rel)
@@ -10,6 +122,46 @@ defmodule Rumbex.Operations do
     end
   end
 
+  def list_dir_counted(conn, rel) do
+    try do
+      Native.list_dir_counted(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def list_dir_ids(conn, rel) do
+    try do
+      Native.list_dir_ids(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def open_by_id(conn, file_id) do
+    try do
+      Native.open_by_id(conn, file_id)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def read_file_by_id(conn, file_id) do
+    try do
+      Native.read_file_by_id(conn, file_id)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def file_stats_by_id(conn, file_id) do
+    try do
+      Native.file_stats_by_id(conn, file_id)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
   def read_file(conn, rel) do
     try do
       Native.read_file(conn, rel)
@@ -18,9 +170,68 @@ defmodule Rumbex.Operations do
     end
   end
 
-  def write_file(conn, rel, bin) do
+  def read_file_info(conn, rel) do
+    try do
+      Native.read_file_info(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def read_file_with_options(conn, rel, options_bits) do
+    try do
+      Native.read_file_with_options(conn, rel, options_bits)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def count_entries(conn, rel, pattern) do
+    try do
+      Native.count_entries(conn, rel, pattern)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def exists_many(conn, rels) do
+    try do
+      Native.exists_many(conn, rels)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def read_file_limited(conn, rel, max_bytes) do
     try do
-      case Native.write_file(conn, rel, bin) do
+      Native.read_file_limited(conn, rel, max_bytes)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def read_files(conn, rels) do
+    try do
+      Native.read_files(conn, rels)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def write_file(conn, rel, bin, sync \\ false, disposition \\ :overwrite, attributes \\ 0) do
+    try do
+      case Native.write_file(conn, rel, bin, sync, disposition, attributes) do
+        {:ok, action} -> {:ok, byte_size(bin), action}
+        other -> other
+      end
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def write_atomic(conn, rel, bin) do
+    try do
+      case Native.write_atomic(conn, rel, bin) do
         :ok -> {:ok, byte_size(bin)}
         other -> other
       end
@@ -29,16 +240,131 @@ defmodule Rumbex.Operations do
     end
   end
 
+  def append_capped(conn, rel, bin, max_size) do
+    try do
+      Native.append_capped(conn, rel, bin, max_size)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def create_exclusive(conn, rel, delete_on_close) do
+    try do
+      Native.create_exclusive(conn, rel, delete_on_close)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def open(conn, rel, access_bits, options_bits) do
+    try do
+      Native.open(conn, rel, access_bits, options_bits)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def open_resource(conn, rel, access_bits, options_bits) do
+    try do
+      Native.open_resource(conn, rel, access_bits, options_bits)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def dup_handle(handle) do
+    try do
+      Native.dup_handle(handle)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def open_in_dir(dir_handle, name, mode) do
+    try do
+      Native.open_in_dir(dir_handle, name, mode)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def reopen(handle) do
+    try do
+      Native.reopen(handle)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def pread(handle, offset, len) do
+    try do
+      Native.pread(handle, offset, len)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def close(handle) do
+    try do
+      Native.close(handle)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def open_writer(conn, rel, disposition) do
+    try do
+      Native.open_writer(conn, rel, disposition)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def handle_write(handle, data) do
+    try do
+      Native.handle_write(handle, data)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def write_at_handle(handle, offset, data) do
+    try do
+      Native.write_at_handle(handle, offset, data)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def flush(handle) do
+    try do
+      Native.flush(handle)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def set_position(handle, offset) do
+    try do
+      Native.set_position(handle, offset)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
   def upload_file(conn, local_path, rel) do
-    with {:ok, bin} <- File.read(local_path) do
-      write_file(conn, rel, bin)
+    with {:ok, bin} <- File.read(local_path),
+         {:ok, size, _action} <- write_file(conn, rel, bin) do
+      {:ok, size}
     end
   end
 
-  def download_file(conn, rel, local_path) do
-    case read_file(conn, rel) do
-      {:ok, bin} -> File.write(local_path, bin)
-      {:error, r} -> {:error, r}
+  def download_file(conn, rel, local_path, max_inflight \\ 4) do
+    try do
+      local_path = IO.chardata_to_string(local_path)
+      with {:ok, _size} <- Native.download_file(conn, rel, local_path, max_inflight), do: :ok
+    rescue
+      e in ErlangError -> {:error, e.original}
     end
   end
 
@@ -58,9 +384,137 @@ defmodule Rumbex.Operations do
     end
   end
 
-  def move_file(conn, from_rel, to_rel) do
+  def rename_in_dir(conn, dir, old_name, new_name) do
+    try do
+      Native.rename_in_dir(conn, dir, old_name, new_name)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def rm_rf(conn, rel, follow_symlinks, dry_run, skip_offline \\ false) do
+    try do
+      Native.rm_rf(conn, rel, follow_symlinks, dry_run, skip_offline)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def list_snapshots(conn, rel) do
     try do
-      Native.rename(conn, from_rel, to_rel, false)
+      Native.list_snapshots(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def cancel_token do
+    try do
+      Native.cancel_token()
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def cancel(token) do
+    try do
+      Native.cancel(token)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def stream_lines(conn, rel, pid, token) do
+    try do
+      Native.stream_lines(conn, rel, pid, token)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def set_info(conn, rel, info_class, buffer) do
+    try do
+      Native.set_info(conn, rel, info_class, buffer)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def query_info_raw(conn, rel, info_class) do
+    try do
+      Native.query_info_raw(conn, rel, info_class)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def real_path(conn, rel) do
+    try do
+      Native.real_path(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def list_names(conn, rel, pattern) do
+    try do
+      Native.list_names(conn, rel, pattern)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def list_dir_class(conn, rel, class) do
+    try do
+      Native.list_dir_class(conn, rel, class)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def list_dir_both_names(conn, rel) do
+    try do
+      Native.list_dir_both_names(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def set_timeout(conn, ms) do
+    try do
+      Native.set_timeout(conn, ms)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def walk_parallel(conn, rel, pid, workers, skip_offline \\ false) do
+    try do
+      Native.walk_parallel(conn, rel, pid, workers, skip_offline)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def set_times_many(conn, items) do
+    try do
+      Native.set_times_many(conn, items)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def set_attributes_many(conn, items) do
+    try do
+      Native.set_attributes_many(conn, items)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def move_file(conn, from_rel, to_rel, dry_run \\ false) do
+    try do
+      Native.rename(conn, from_rel, to_rel, false, dry_run)
     rescue
       e in ErlangError -> {:error, e.original}
     end
@@ -78,12 +532,59 @@ defmodule Rumbex.Operations do
     end
   end
 
-  def get_file_stats(conn, rel) do
+  def get_file_stats(conn, rel, options_bits \\ 0) do
     try do
-      case Native.file_stats(conn, rel) do
-        {:ok, :not_found} -> {:error, :enoent}
-        other -> other
-      end
+      if options_bits == 0,
+        do: Native.file_stats(conn, rel),
+        else: Native.file_stats_with_options(conn, rel, options_bits)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def get_owner(conn, rel) do
+    try do
+      Native.get_owner(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def set_owner(conn, rel, sid) do
+    try do
+      Native.set_owner(conn, rel, sid)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def set_dacl_protected(conn, rel, protected) do
+    try do
+      Native.set_dacl_protected(conn, rel, protected)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def newer_than(conn, rel, unix_seconds) do
+    try do
+      Native.newer_than(conn, rel, unix_seconds)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def stat_attrs(conn, rel) do
+    try do
+      Native.stat_attrs(conn, rel)
+    rescue
+      e in ErlangError -> {:error, e.original}
+    end
+  end
+
+  def is_junction(conn, rel) do
+    try do
+      Native.is_junction(conn, rel)
     rescue
       e in ErlangError -> {:error, e.original}
     end
@@ -97,9 +598,9 @@ defmodule Rumbex.Operations do
     end
   end
 
-  def delete_file(conn, rel) do
+  def delete_file(conn, rel, dry_run \\ false, wait \\ false) do
     try do
-      Native.rm(conn, rel)
+      Native.rm(conn, rel, dry_run, wait)
     rescue
       e in ErlangError -> {:error, e.original}
     end
@@ -2,6 +2,7 @@ defmodule Rumbex.Path do
   @moduledoc false
 
   # "smb://host/share/rel" -> {"\\host\share", "rel"}
+  # Segments are percent-decoded, so admin shares may be written "C$" or "C%24".
   @spec parse_smb_url!(String.t()) :: {String.t(), String.t()}
   def parse_smb_url!("smb://" <> _ = url) do
     uri = URI.parse(url)
@@ -11,6 +12,7 @@ defmodule Rumbex.Path do
       (uri.path || "")
       |> String.trim_leading("/")
       |> String.split("/", trim: true)
+      |> Enum.map(&URI.decode/1)
       |> case do
         [sh | _] = all when sh != "" -> all
         _ -> raise ArgumentError, "bad SMB url: share is missing"
@@ -29,6 +31,29 @@ defmodule Rumbex.Path do
     end
   end
 
+  # "//host/share/rel" (URL-style UNC) -> as "\\host\share\rel"
+  def parse_smb_url!("//" <> _ = unc), do: parse_smb_url!(String.replace(unc, "/", "\\"))
+
+  # "smb://host[/...]" | "\\host[\...]" | "//host[/...]" | "host" -> "host"
+  @spec parse_host!(String.t()) :: String.t()
+  def parse_host!("smb://" <> _ = url) do
+    case URI.parse(url) do
+      %URI{host: host, port: nil} when host not in [nil, ""] -> host
+      %URI{host: host, port: port} when host not in [nil, ""] -> "#{host}:#{port}"
+      _ -> raise ArgumentError, "bad SMB url: host is missing"
+    end
+  end
+
+  def parse_host!(<<"\\\\", rest::binary>>), do: parse_host!(rest)
+  def parse_host!("//" <> rest), do: parse_host!(rest)
+
+  def parse_host!(host) when is_binary(host) do
+    case String.split(host, ["\\", "/"], parts: 2) do
+      [h | _] when h != "" -> h
+      _ -> raise ArgumentError, "bad SMB host"
+    end
+  end
+
   @spec norm(String.t()) :: String.t()
   def norm(path) do
     path
@@ -5,6 +5,7 @@ defmodule Rumbex.Pool do
   """
 
   use GenServer
+  require Logger
   alias Rumbex.Operations
   alias Rumbex.Path
   alias Rumbex.Native
@@ -15,6 +16,7 @@ defmodule Rumbex.Pool do
           | {:username, String.t()}
           | {:password, String.t()}
           | {:size, pos_integer()}
+          | {:conn_opts, map()}
 
   ## ===== Public API =====
 
@@ -34,17 +36,123 @@ defmodule Rumbex.Pool do
 
   # Operations. All call the corresponding handle_call without any anonymous functions.
   def list_dir(pool, path), do: GenServer.call(pool, {:list_dir, path}, :infinity)
+  def list_dir_counted(pool, path), do: GenServer.call(pool, {:list_dir_counted, path}, :infinity)
   def read_file(pool, path), do: GenServer.call(pool, {:read_file, path}, :infinity)
-  def write_file(pool, p, data), do: GenServer.call(pool, {:write_file, p, data}, :infinity)
+  def write_file(pool, p, data, sync \\ false, disposition \\ :overwrite, attributes \\ 0),
+    do: GenServer.call(pool, {:write_file, p, data, sync, disposition, attributes}, :infinity)
+
+  def write_atomic(pool, p, data), do: GenServer.call(pool, {:write_atomic, p, data}, :infinity)
+
+  def append_capped(pool, p, data, max_size),
+    do: GenServer.call(pool, {:append_capped, p, data, max_size}, :infinity)
+
+  def create_exclusive(pool, path, doc),
+    do: GenServer.call(pool, {:create_exclusive, path, doc}, :infinity)
+
   def upload_file(pool, lp, rp), do: GenServer.call(pool, {:upload_file, lp, rp}, :infinity)
-  def download_file(pool, rp, lp), do: GenServer.call(pool, {:download_file, rp, lp}, :infinity)
+  def download_file(pool, rp, lp, max_inflight \\ 4),
+    do: GenServer.call(pool, {:download_file, rp, lp, max_inflight}, :infinity)
   def mkdir(pool, path), do: GenServer.call(pool, {:mkdir, path}, :infinity)
   def mkdir_p(pool, path), do: GenServer.call(pool, {:mkdir_p, path}, :infinity)
-  def move_file(pool, a, b), do: GenServer.call(pool, {:move_file, a, b}, :infinity)
+  def move_file(pool, a, b, dry \\ false),
+    do: GenServer.call(pool, {:move_file, a, b, dry}, :infinity)
+
   def get_stat(pool, path), do: GenServer.call(pool, {:get_stat, path}, :infinity)
-  def get_file_stats(pool, path), do: GenServer.call(pool, {:get_file_stats, path}, :infinity)
+  def get_file_stats(pool, path, options_bits \\ 0),
+    do: GenServer.call(pool, {:get_file_stats, path, options_bits}, :infinity)
   def exists(pool, path), do: GenServer.call(pool, {:exists, path}, :infinity)
-  def delete_file(pool, path), do: GenServer.call(pool, {:delete_file, path}, :infinity)
+  def exists_many(pool, paths), do: GenServer.call(pool, {:exists_many, paths}, :infinity)
+  def delete_file(pool, path, dry \\ false, wait \\ false),
+    do: GenServer.call(pool, {:delete_file, path, dry, wait}, :infinity)
+
+  def server_info(pool), do: GenServer.call(pool, :server_info, :infinity)
+  def list_dir_ids(pool, path), do: GenServer.call(pool, {:list_dir_ids, path}, :infinity)
+  def open_by_id(pool, file_id), do: GenServer.call(pool, {:open_by_id, file_id}, :infinity)
+
+  def read_file_by_id(pool, file_id),
+    do: GenServer.call(pool, {:read_file_by_id, file_id}, :infinity)
+
+  def file_stats_by_id(pool, file_id),
+    do: GenServer.call(pool, {:file_stats_by_id, file_id}, :infinity)
+
+  def open(pool, path, access_bits, options_bits),
+    do: GenServer.call(pool, {:open, path, access_bits, options_bits}, :infinity)
+
+  def open_resource(pool, path, access_bits, options_bits),
+    do: GenServer.call(pool, {:open_resource, path, access_bits, options_bits}, :infinity)
+
+  def stat_attrs(pool, path), do: GenServer.call(pool, {:stat_attrs, path}, :infinity)
+
+  def rename_in_dir(pool, dir, old_name, new_name),
+    do: GenServer.call(pool, {:rename_in_dir, dir, old_name, new_name}, :infinity)
+
+  def is_junction(pool, path), do: GenServer.call(pool, {:is_junction, path}, :infinity)
+  def read_files(pool, paths), do: GenServer.call(pool, {:read_files, paths}, :infinity)
+
+  def read_file_limited(pool, path, max_bytes),
+    do: GenServer.call(pool, {:read_file_limited, path, max_bytes}, :infinity)
+
+  def rm_rf(pool, path, follow_symlinks, dry_run, skip_offline \\ false),
+    do: GenServer.call(pool, {:rm_rf, path, follow_symlinks, dry_run, skip_offline}, :infinity)
+
+  def list_snapshots(pool, path), do: GenServer.call(pool, {:list_snapshots, path}, :infinity)
+
+  def stream_lines(pool, path, pid, token),
+    do: GenServer.call(pool, {:stream_lines, path, pid, token}, :infinity)
+
+  def set_info(pool, path, info_class, buffer),
+    do: GenServer.call(pool, {:set_info, path, info_class, buffer}, :infinity)
+
+  def query_info_raw(pool, path, info_class),
+    do: GenServer.call(pool, {:query_info_raw, path, info_class}, :infinity)
+
+  def real_path(pool, path), do: GenServer.call(pool, {:real_path, path}, :infinity)
+
+  def list_dir_both_names(pool, path),
+    do: GenServer.call(pool, {:list_dir_both_names, path}, :infinity)
+
+  def set_timeout(pool, ms), do: GenServer.call(pool, {:set_timeout, ms}, :infinity)
+
+  def walk_parallel(pool, root, pid, workers, skip_offline \\ false),
+    do: GenServer.call(pool, {:walk_parallel, root, pid, workers, skip_offline}, :infinity)
+
+  def set_times_many(pool, items),
+    do: GenServer.call(pool, {:set_times_many, items}, :infinity)
+
+  def set_attributes_many(pool, items),
+    do: GenServer.call(pool, {:set_attributes_many, items}, :infinity)
+
+  def open_writer(pool, path, disposition),
+    do: GenServer.call(pool, {:open_writer, path, disposition}, :infinity)
+
+  def read_file_info(pool, path), do: GenServer.call(pool, {:read_file_info, path}, :infinity)
+
+  def read_file_with_options(pool, path, options_bits),
+    do: GenServer.call(pool, {:read_file_with_options, path, options_bits}, :infinity)
+
+  def count_entries(pool, path, pattern),
+    do: GenServer.call(pool, {:count_entries, path, pattern}, :infinity)
+
+  def get_owner(pool, path), do: GenServer.call(pool, {:get_owner, path}, :infinity)
+  def set_owner(pool, path, sid), do: GenServer.call(pool, {:set_owner, path, sid}, :infinity)
+
+  def set_dacl_protected(pool, path, protected),
+    do: GenServer.call(pool, {:set_dacl_protected, path, protected}, :infinity)
+
+  def list_dir_class(pool, path, class),
+    do: GenServer.call(pool, {:list_dir_class, path, class}, :infinity)
+
+  def list_names(pool, path, pattern),
+    do: GenServer.call(pool, {:list_names, path, pattern}, :infinity)
+
+  def share_readonly(pool), do: GenServer.call(pool, :share_readonly, :infinity)
+
+  def newer_than(pool, path, unix_seconds),
+    do: GenServer.call(pool, {:newer_than, path, unix_seconds}, :infinity)
+
+
+
+  def resolve(pool, path), do: GenServer.call(pool, {:resolve, path}, :infinity)
   def refresh(pool, which \\ :all), do: GenServer.call(pool, {:refresh, which}, :infinity)
 
   ## ===== GenServer =====
@@ -55,11 +163,14 @@ defmodule Rumbex.Pool do
     user = Keyword.fetch!(opts, :username)
     pass = Keyword.fetch!(opts, :password)
     size = Keyword.get(opts, :size, 5) |> max(1)
+    conn_opts = Keyword.get(opts, :conn_opts, %{})
 
     {unc, _rel} = Path.parse_smb_url!(url)
-    conns = for _ <- 1..size, do: connect!(unc, user, pass)
+    conns = for _ <- 1..size, do: connect!(unc, user, pass, conn_opts)
+    # a cancel token only guards these first connects, not later reconnects
+    conn_opts = Map.delete(conn_opts, :cancel)
 
-    {:ok, %{unc: unc, user: user, pass: pass, conns: conns, next: 0}}
+    {:ok, %{unc: unc, user: user, pass: pass, conn_opts: conn_opts, conns: conns, next: 0}}
   rescue
     e in ArgumentError -> {:stop, e.message}
     e in ErlangError -> {:stop, e.original}
@@ -75,14 +186,40 @@ defmodule Rumbex.Pool do
     {:reply, reply, s2}
   end
 
+  def handle_call({:list_dir_counted, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.list_dir_counted(conn, Path.norm(path)), s2}
+  end
+
   def handle_call({:read_file, path}, _f, s) do
     {conn, s2} = checkout(s)
     {:reply, Operations.read_file(conn, Path.norm(path)), s2}
   end
 
-  def handle_call({:write_file, path, data}, _f, s) do
+  def handle_call({:write_file, path, data, sync, disposition}, f, s),
+    do: handle_call({:write_file, path, data, sync, disposition, 0}, f, s)
+
+  def handle_call({:write_file, path, data, sync, disposition, attributes}, _f, s) do
     {conn, s2} = checkout(s)
-    {:reply, Operations.write_file(conn, Path.norm(path), IO.iodata_to_binary(data)), s2}
+    bin = IO.iodata_to_binary(data)
+    reply = Operations.write_file(conn, Path.norm(path), bin, sync, disposition, attributes)
+    {:reply, reply, s2}
+  end
+
+  def handle_call({:write_atomic, path, data}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.write_atomic(conn, Path.norm(path), IO.iodata_to_binary(data)), s2}
+  end
+
+  def handle_call({:append_capped, path, data, max_size}, _f, s) do
+    {conn, s2} = checkout(s)
+    bin = IO.iodata_to_binary(data)
+    {:reply, Operations.append_capped(conn, Path.norm(path), bin, max_size), s2}
+  end
+
+  def handle_call({:create_exclusive, path, delete_on_close}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.create_exclusive(conn, Path.norm(path), delete_on_close), s2}
   end
 
   def handle_call({:upload_file, local, remote}, _f, s) do
@@ -90,9 +227,9 @@ defmodule Rumbex.Pool do
     {:reply, Operations.upload_file(conn, local, Path.norm(remote)), s2}
   end
 
-  def handle_call({:download_file, remote, local}, _f, s) do
+  def handle_call({:download_file, remote, local, max_inflight}, _f, s) do
     {conn, s2} = checkout(s)
-    {:reply, Operations.download_file(conn, Path.norm(remote), local), s2}
+    {:reply, Operations.download_file(conn, Path.norm(remote), local, max_inflight), s2}
   end
 
   def handle_call({:mkdir, path}, _f, s) do
@@ -105,9 +242,9 @@ defmodule Rumbex.Pool do
     {:reply, Operations.mkdir_p(conn, Path.norm(path)), s2}
   end
 
-  def handle_call({:move_file, from, to}, _f, s) do
+  def handle_call({:move_file, from, to, dry_run}, _f, s) do
     {conn, s2} = checkout(s)
-    {:reply, Operations.move_file(conn, Path.norm(from), Path.norm(to)), s2}
+    {:reply, Operations.move_file(conn, Path.norm(from), Path.norm(to), dry_run), s2}
   end
 
   def handle_call({:get_stat, path}, _f, s) do
@@ -115,9 +252,12 @@ defmodule Rumbex.Pool do
     {:reply, Operations.get_stat(conn, Path.norm(path)), s2}
   end
 
-  def handle_call({:get_file_stats, path}, _f, s) do
+  def handle_call({:get_file_stats, path}, f, s),
+    do: handle_call({:get_file_stats, path, 0}, f, s)
+
+  def handle_call({:get_file_stats, path, options_bits}, _f, s) do
     {conn, s2} = checkout(s)
-    {:reply, Operations.get_file_stats(conn, Path.norm(path)), s2}
+    {:reply, Operations.get_file_stats(conn, Path.norm(path), options_bits), s2}
   end
 
   def handle_call({:exists, path}, _f, s) do
@@ -125,9 +265,214 @@ defmodule Rumbex.Pool do
     {:reply, Operations.exists(conn, Path.norm(path)), s2}
   end
 
-  def handle_call({:delete_file, path}, _f, s) do
+  def handle_call({:delete_file, path, dry_run, wait}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.delete_file(conn, Path.norm(path), dry_run, wait), s2}
+  end
+
+  def handle_call(:server_info, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.server_info(conn), s2}
+  end
+
+  def handle_call({:list_dir_ids, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.list_dir_ids(conn, Path.norm(path)), s2}
+  end
+
+  def handle_call({:open_by_id, file_id}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.open_by_id(conn, file_id), s2}
+  end
+
+  def handle_call({:read_file_by_id, file_id}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.read_file_by_id(conn, file_id), s2}
+  end
+
+  def handle_call({:file_stats_by_id, file_id}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.file_stats_by_id(conn, file_id), s2}
+  end
+
+  def handle_call({:open, path, access_bits, options_bits}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.open(conn, Path.norm(path), access_bits, options_bits), s2}
+  end
+
+  def handle_call({:open_resource, path, access_bits, options_bits}, _f, s) do
+    {conn, s2} = checkout(s)
+    reply = Operations.open_resource(conn, Path.norm(path), access_bits, options_bits)
+    {:reply, reply, s2}
+  end
+
+  def handle_call({:stat_attrs, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.stat_attrs(conn, Path.norm(path)), s2}
+  end
+
+  def handle_call({:rename_in_dir, dir, old_name, new_name}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.rename_in_dir(conn, Path.norm(dir), old_name, new_name), s2}
+  end
+
+  def handle_call({:is_junction, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.is_junction(conn, Path.norm(path)), s2}
+  end
+
+  def handle_call({:exists_many, paths}, _f, s) do
+    {conn, s2} = checkout(s)
+
+    # keyed by the caller's paths, as for read_files
+    reply =
+      with {:ok, kinds} <- Operations.exists_many(conn, Enum.map(paths, &Path.norm/1)) do
+        {:ok, Enum.zip_with(paths, kinds, fn path, {_norm, kind} -> {path, kind} end)}
+      end
+
+    {:reply, reply, s2}
+  end
+
+  def handle_call({:read_files, paths}, _f, s) do
+    {conn, s2} = checkout(s)
+
+    # results come back keyed by the caller's paths, not the normalized ones
+    reply =
+      with {:ok, results} <- Operations.read_files(conn, Enum.map(paths, &Path.norm/1)) do
+        {:ok, Enum.zip_with(paths, results, fn path, {_norm, r} -> {path, r} end)}
+      end
+
+    {:reply, reply, s2}
+  end
+
+  def handle_call({:read_file_limited, path, max_bytes}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.read_file_limited(conn, Path.norm(path), max_bytes), s2}
+  end
+
+  def handle_call({:rm_rf, path, follow_symlinks, dry_run, skip_offline}, _f, s) do
+    {conn, s2} = checkout(s)
+    reply = Operations.rm_rf(conn, Path.norm(path), follow_symlinks, dry_run, skip_offline)
+    {:reply, reply, s2}
+  end
+
+  def handle_call({:list_snapshots, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.list_snapshots(conn, Path.norm(path)), s2}
+  end
+
+  def handle_call({:stream_lines, path, pid, token}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.stream_lines(conn, Path.norm(path), pid, token), s2}
+  end
+
+  def handle_call({:set_info, path, info_class, buffer}, _f, s) do
     {conn, s2} = checkout(s)
-    {:reply, Operations.delete_file(conn, Path.norm(path)), s2}
+    {:reply, Operations.set_info(conn, Path.norm(path), info_class, buffer), s2}
+  end
+
+  def handle_call({:query_info_raw, path, info_class}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.query_info_raw(conn, Path.norm(path), info_class), s2}
+  end
+
+  def handle_call({:real_path, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.real_path(conn, Path.norm(path)), s2}
+  end
+
+  def handle_call({:list_dir_both_names, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.list_dir_both_names(conn, Path.norm(path)), s2}
+  end
+
+  # items are {path, ...} tuples; results come back keyed by the caller's paths
+  def handle_call({op, items}, _f, s) when op in [:set_times_many, :set_attributes_many] do
+    {conn, s2} = checkout(s)
+    normalized = Enum.map(items, &put_elem(&1, 0, Path.norm(elem(&1, 0))))
+
+    reply =
+      with {:ok, results} <- apply(Operations, op, [conn, normalized]) do
+        {:ok, Enum.zip_with(items, results, fn item, {_norm, r} -> {elem(item, 0), r} end)}
+      end
+
+    {:reply, reply, s2}
+  end
+
+  # every Conn of the pool, and the ones reconnected later
+  def handle_call({:set_timeout, ms}, _f, s) do
+    reply =
+      s.conns
+      |> Enum.map(&Operations.set_timeout(&1, ms))
+      |> Enum.find(:ok, &(&1 != :ok))
+
+    {:reply, reply, %{s | conn_opts: Map.put(s.conn_opts, :timeout_ms, ms)}}
+  end
+
+  def handle_call({:walk_parallel, root, pid, workers, skip_offline}, _f, s) do
+    {conn, s2} = checkout(s)
+    reply = Operations.walk_parallel(conn, Path.norm(root), pid, workers, skip_offline)
+    {:reply, reply, s2}
+  end
+
+  def handle_call({:open_writer, path, disposition}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.open_writer(conn, Path.norm(path), disposition), s2}
+  end
+
+  def handle_call({:read_file_info, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.read_file_info(conn, Path.norm(path)), s2}
+  end
+
+  def handle_call({:read_file_with_options, path, options_bits}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.read_file_with_options(conn, Path.norm(path), options_bits), s2}
+  end
+
+  def handle_call({:count_entries, path, pattern}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.count_entries(conn, Path.norm(path), pattern), s2}
+  end
+
+  def handle_call({:get_owner, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.get_owner(conn, Path.norm(path)), s2}
+  end
+
+  def handle_call({:set_owner, path, sid}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.set_owner(conn, Path.norm(path), sid), s2}
+  end
+
+  def handle_call({:set_dacl_protected, path, protected}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.set_dacl_protected(conn, Path.norm(path), protected), s2}
+  end
+
+  def handle_call({:list_dir_class, path, class}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.list_dir_class(conn, Path.norm(path), class), s2}
+  end
+
+  def handle_call({:list_names, path, pattern}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.list_names(conn, Path.norm(path), pattern), s2}
+  end
+
+  def handle_call(:share_readonly, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.share_readonly(conn), s2}
+  end
+
+  def handle_call({:newer_than, path, unix_seconds}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.newer_than(conn, Path.norm(path), unix_seconds), s2}
+  end
+
+  def handle_call({:resolve, path}, _f, s) do
+    {conn, s2} = checkout(s)
+    {:reply, Operations.resolve(conn, Path.norm(path)), s2}
   end
 
   def handle_call({:refresh, :all}, _f, s) do
@@ -144,6 +489,16 @@ defmodule Rumbex.Pool do
     end
   end
 
+  # A pooled Conn got past a client lock poisoned by a panic in the NIF (see
+  # Conn::lock_recovering); `how` is :kept (session still fine) or :reconnected.
+  @impl GenServer
+  def handle_info({:rumbex_lock_recovered, share, how}, s) do
+    Logger.warning("[Rumbex.Pool] client lock for #{share} poisoned by a panic, #{how}")
+    {:noreply, s}
+  end
+
+  def handle_info(_msg, s), do: {:noreply, s}
+
   ## ===== internal =====
 
   defp checkout(%{conns: [one]} = s), do: {one, s}
@@ -154,25 +509,40 @@ defmodule Rumbex.Pool do
     {Enum.at(conns, idx), %{s | next: idx + 1}}
   end
 
-  defp reconnect_all(%{unc: unc, user: u, pass: p, conns: conns} = s) do
-    new = Enum.map(conns, fn _ -> connect!(unc, u, p) end)
+  defp reconnect_all(%{unc: unc, user: u, pass: p, conn_opts: o, conns: conns} = s) do
+    new = Enum.map(conns, fn _ -> connect!(unc, u, p, o) end)
     {:ok, %{s | conns: new, next: 0}}
   rescue
     e -> {:error, Exception.message(e)}
   end
 
-  defp reconnect_one(%{unc: unc, user: u, pass: p, conns: conns} = s, idx) do
+  defp reconnect_one(%{unc: unc, user: u, pass: p, conn_opts: o, conns: conns} = s, idx) do
     if idx >= length(conns), do: {:error, :bad_index}, else: :ok
-    new = List.replace_at(conns, idx, connect!(unc, u, p))
+    new = List.replace_at(conns, idx, connect!(unc, u, p, o))
     {:ok, %{s | conns: new}}
   rescue
     e -> {:error, Exception.message(e)}
   end
 
-  defp connect!(unc, user, pass) do
-    case Native.connect(unc, user, pass) do
+  defp connect!(unc, user, pass, conn_opts) do
+    case native_connect(unc, user, pass, conn_opts) do
       {:ok, conn} -> conn
+      # :share_not_found, :auth_failed, ... reach the caller as-is (see init/1)
+      {:error, r} when is_atom(r) -> :erlang.error(r)
       {:error, r} -> raise "connect_failed: #{inspect(r)}"
     end
   end
+
+  defp native_connect(unc, user, _pass, %{nt_hash: hash} = conn_opts) do
+    {domain, conn_opts} = Map.pop(conn_opts, :domain)
+    auth = %{username: user, nt_hash: hash}
+    auth = if domain, do: Map.put(auth, :domain, domain), else: auth
+    Native.connect_with_hash(unc, auth, Map.drop(conn_opts, [:nt_hash, :kerberos]))
+  end
+
+  defp native_connect(unc, user, pass, %{kerberos: true} = conn_opts),
+    do: Native.connect_kerberos(unc, user, pass, Map.delete(conn_opts, :kerberos))
+
+  defp native_connect(unc, user, pass, conn_opts),
+    do: Native.connect_with_opts(unc, user, pass, Map.delete(conn_opts, :kerberos))
 end
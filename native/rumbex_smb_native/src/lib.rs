@@ -1,49 +1,465 @@
 #![allow(non_local_definitions)]
-use rustler::{Env, NifResult, ResourceArc, Term, Encoder, Atom, Binary, NifMap};
+use rustler::{Env, NifResult, ResourceArc, Term, Encoder, Atom, Binary, NifMap, LocalPid, OwnedEnv};
 use rustler::types::binary::OwnedBinary;
 
 use std::{
+    collections::{HashSet, VecDeque},
     convert::TryInto,
-    io::{Read, Write},
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    net::{SocketAddr, ToSocketAddrs},
     str::FromStr,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Condvar, Mutex, MutexGuard, Weak,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+use socket2::{Domain, Protocol, Socket, Type};
+use zeroize::Zeroizing;
+use binrw::{BinRead, BinWrite};
+
 use smb::{
     client::{Client, ClientConfig, UncPath},
     packets::{
         fscc::{
             FileAccessMask,
             FileAttributes,
-            common_info::FileBasicInformation,
-            query_file_info::FileStandardInformation,
-            set_file_info::FileRenameInformation2,
-            directory_info::FileIdFullDirectoryInformation,
+            common_info::{FileBasicInformation, FileModeInformation, FilePositionInformation},
+            query_file_info::{
+                FileAccessInformation, FileAlignmentInformation, FileAllInformation,
+                FileAttributeTagInformation,
+                FileCompressionInformation, FileEaInformation, FileIdInformation,
+                FileInternalInformation, FileNetworkOpenInformation,
+                FileNormalizedNameInformation, FileStandardInformation,
+            },
+            set_file_info::{
+                FileAllocationInformation, FileDispositionInformation, FileEndOfFileInformation,
+                FileRenameInformation2, FileValidDataLengthInformation,
+            },
+            directory_info::{
+                FileBothDirectoryInformation, FileFullDirectoryInformation,
+                FileIdFullDirectoryInformation, FileNamesInformation, QueryDirectoryInfoValue,
+            },
         },
         binrw_util::{
             sized_wide_string::SizedWideString,
             helpers::Boolean,
         },
-        smb2::{CreateOptions, CreateDisposition},
-        
+        smb2::{
+            AdditionalInfo, CreateOptions, CreateDisposition,
+            SrvEnumerateSnapshotsRequest,
+            Dialect, EncryptionCapabilities, EncryptionCipher, GlobalCapabilities,
+            HashAlgorithm, NegotiateContextValue, NegotiateRequest, NegotiateResponse,
+            NegotiateSecurityMode, PlainRequest, PlainResponse, PreauthIntegrityCapabilities,
+            RequestContent,
+        },
+        guid::Guid,
+        transport::SmbTcpMessageHeader,
+        security::{SecurityDescriptor, SecurityDescriptorControl, SID},
+        rpc::ndr64::NdrPtr,
     },
     resource::{
         file::File as SmbFile,
         directory::Directory,
         FileCreateArgs,
-        Resource
+        GetLen,
+        Resource,
+        ResourceHandle,
     },
 };
 
-// Client is held in Mutex — Client methods require &mut self
+// Client is held in Mutex — Client methods require &mut self.
+// Arc: the idle watcher holds a weak reference to log it off.
 struct Conn {
-    client: Mutex<Client>,
+    client: Arc<Mutex<Client>>,
+    // NTSTATUS of the last failed operation, 0 once one succeeds (last_status/1)
+    last_status: AtomicU32,
+    io: IoCounters, // io_stats/1
     share: UncPath, // \\host\share
+    // Extra connections to the same share (connect_with_opts `channels: n`);
+    // large read_file/write_file transfers are striped across these + `client`.
+    channels: Vec<Arc<Mutex<Client>>>,
+    // Kept to transparently reconnect after an idle logoff
+    username: String,
+    password: Zeroizing<String>, // wiped when the Conn is dropped
+    opts: ConnectOpts,
+    idle: Option<Arc<IdleState>>, // Some when `idle_timeout_ms` is set
+    // Open read handles kept by read_file for reuse (`handle_cache: n`), most recent
+    // last; keyed by cache_key(path). Empty and unused when n == 0.
+    handles: Mutex<Vec<(String, SmbFile)>>,
+    // Set once any request fails with STATUS_MEDIA_WRITE_PROTECTED (share_readonly/1)
+    write_protected: AtomicBool,
+    owner: LocalPid, // the connecting process; told about lock recoveries
+}
+
+// Idle auto-logoff bookkeeping, shared with the watcher thread
+struct IdleState {
+    timeout: Duration,
+    last_used: Mutex<Instant>,
+    logged_off: AtomicBool,
+}
+
+impl IdleState {
+    fn touch(&self) {
+        if let Ok(mut t) = self.last_used.lock() {
+            *t = Instant::now();
+        }
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_used.lock().map(|t| t.elapsed()).unwrap_or_default()
+    }
+}
+
+impl Conn {
+    // Locks the client. If it was logged off while idle, reconnects it first (with the
+    // stored credentials), so callers never notice the logoff.
+    fn lock_client(&self) -> NifResult<MutexGuard<'_, Client>> {
+        let mut guard = self.lock_recovering(&self.client)?;
+
+        if let Some(idle) = &self.idle {
+            if idle.logged_off.load(Ordering::Acquire) {
+                self.reconnect(&mut guard)?;
+                idle.logged_off.store(false, Ordering::Release);
+            }
+            idle.touch();
+        }
+
+        // a new operation starts; failures below record their status again
+        self.last_status.store(0, Ordering::Release);
+
+        Ok(guard)
+    }
+
+    // Locks `client` (the main one or a channel). A panic while it was held poisons the
+    // mutex but seldom breaks the Client itself, so instead of failing every later call
+    // the session is checked with an open of the share root and kept if it answers,
+    // replaced by a fresh connection if not. The mutex stays poisoned (and the next lock
+    // tries again) only when reconnecting fails too.
+    fn lock_recovering<'m>(&self, client: &'m Mutex<Client>) -> NifResult<MutexGuard<'m, Client>> {
+        let access = FileAccessMask::new().with_file_read_attributes(true);
+        lock_recovering(
+            client,
+            &self.owner,
+            &self.share.to_string(),
+            |c| open_share_root(c, &self.share, access).is_ok(),
+            || {
+                let fresh = self.fresh_client()?;
+                self.clear_handles();
+                Ok(fresh)
+            },
+        )
+    }
+
+    // Takes the cached read handle for `rel`, if any (the caller puts it back).
+    fn take_cached_handle(&self, rel: &str) -> Option<SmbFile> {
+        if self.opts.handle_cache == 0 {
+            return None;
+        }
+        let key = cache_key(rel);
+        let mut handles = self.handles.lock().ok()?;
+        let at = handles.iter().position(|(k, _)| *k == key)?;
+        Some(handles.remove(at).1)
+    }
+
+    // Keeps `file` open for the next read of `rel`; the least recently used handle
+    // beyond `handle_cache` is closed.
+    fn cache_handle(&self, rel: &str, file: SmbFile) {
+        if self.opts.handle_cache == 0 {
+            return;
+        }
+        if let Ok(mut handles) = self.handles.lock() {
+            handles.push((cache_key(rel), file));
+            if handles.len() > self.opts.handle_cache {
+                handles.remove(0); // dropped -> CLOSE
+            }
+        }
+    }
+
+    // Drops the cached handle of `rel`; called by everything that writes, renames
+    // or deletes through this Conn.
+    fn forget_handle(&self, rel: &str) {
+        drop(self.take_cached_handle(rel));
+    }
+
+    fn clear_handles(&self) {
+        if let Ok(mut handles) = self.handles.lock() {
+            handles.clear();
+        }
+    }
+
+    // Remembers the NTSTATUS carried by `e` (0 if it has none) for last_status/1.
+    fn note_status<E: std::fmt::Display>(&self, e: &E) {
+        let status = ntstatus_from_err_display(e).unwrap_or(0);
+        self.last_status.store(status, Ordering::Release);
+        if status == STATUS_MEDIA_WRITE_PROTECTED {
+            self.write_protected.store(true, Ordering::Release);
+        }
+    }
+
+    // create_error, recording the status on this connection.
+    fn create_error<E: std::fmt::Display>(&self, what: &str, e: &E) -> rustler::Error {
+        self.note_status(e);
+        create_error(what, e)
+    }
+
+    // create_error for the open of a file to read or write: a missing file or parent
+    // directory is `:not_found`, as for exists/file_stats.
+    fn open_error<E: std::fmt::Display>(&self, what: &str, e: &E) -> rustler::Error {
+        match ntstatus_from_err_display(e) {
+            Some(STATUS_OBJECT_NAME_NOT_FOUND) | Some(STATUS_OBJECT_PATH_NOT_FOUND) => {
+                self.note_status(e);
+                rustler::Error::Term(Box::new(atoms::not_found()))
+            }
+            _ => self.create_error(what, e),
+        }
+    }
+
+    // write_error, recording the status on this connection.
+    fn write_error<E: std::fmt::Display>(&self, what: &str, e: &E) -> rustler::Error {
+        self.note_status(e);
+        write_error(what, e)
+    }
+
+    // An already formatted write failure (striped / temp-file paths): `:no_space` for a
+    // full disk, else the reason string as is.
+    fn write_failure(&self, reason: String) -> rustler::Error {
+        self.note_status(&reason);
+        match ntstatus_from_err_display(&reason) {
+            Some(STATUS_DISK_FULL) => rustler::Error::Term(Box::new(atoms::no_space())),
+            _ => rustler::Error::Term(Box::new(reason)),
+        }
+    }
+
+    // "what: e" error term, recording the status on this connection.
+    fn op_error<E: std::fmt::Display>(&self, what: &str, e: &E) -> rustler::Error {
+        self.note_status(e);
+        rustler::Error::Term(Box::new(format!("{what}: {e}")))
+    }
+
+    // Replaces the (locked) main client and every channel with freshly connected ones.
+    // Cached handles belonged to the old sessions and are dropped.
+    fn reconnect(&self, guard: &mut MutexGuard<'_, Client>) -> NifResult<()> {
+        **guard = self.fresh_client()?;
+        for ch in &self.channels {
+            // replaced anyway, so a poisoned channel needs no health check
+            let mut client = ch.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            *client = self.fresh_client()?;
+            ch.clear_poison();
+        }
+        self.clear_handles();
+        Ok(())
+    }
+
+    fn fresh_client(&self) -> NifResult<Client> {
+        let mut client = Client::new(client_config(&self.opts));
+        client
+            .share_connect(&self.share, &self.username, self.password.as_str().to_owned())
+            .map_err(|e| connect_error("reconnect_error", &e))?;
+        Ok(client)
+    }
+}
+
+// Conn::lock_recovering / Session::lock_client: on a poisoned `client`, keeps it if
+// `alive` says the session still answers, else swaps in `fresh()`. Either way `owner`
+// gets `{:rumbex_lock_recovered, target, :kept | :reconnected}`.
+fn lock_recovering<'m>(
+    client: &'m Mutex<Client>,
+    owner: &LocalPid,
+    target: &str,
+    alive: impl FnOnce(&mut Client) -> bool,
+    fresh: impl FnOnce() -> NifResult<Client>,
+) -> NifResult<MutexGuard<'m, Client>> {
+    let poisoned = match client.lock() {
+        Ok(guard) => return Ok(guard),
+        Err(poisoned) => poisoned,
+    };
+    let mut guard = poisoned.into_inner();
+    let kept = alive(&mut guard);
+    if !kept {
+        *guard = fresh()?;
+    }
+    client.clear_poison();
+    notify_recovered(*owner, target.to_string(), kept);
+    Ok(guard)
+}
+
+// Sends the lock recovery notice from a thread of its own: NIFs run on (dirty)
+// scheduler threads, where OwnedEnv can't send and no caller Env is at hand here.
+fn notify_recovered(owner: LocalPid, target: String, kept: bool) {
+    std::thread::spawn(move || {
+        let mut msg_env = OwnedEnv::new();
+        let how = if kept { atoms::kept } else { atoms::reconnected };
+        let _ = msg_env.send_and_clear(&owner, |e| {
+            (atoms::rumbex_lock_recovered(), target, how()).encode(e)
+        });
+    });
+}
+
+// An open SMB handle owned by the BEAM. Closed by close/1 (Option taken) or,
+// failing that, when the resource is garbage collected. Keeps its Conn alive.
+struct FileHandle {
+    res: Mutex<Option<Resource>>,
+    conn: ResourceArc<Conn>,
+    path: String, // share-relative, for diagnostics
+    // How it was opened (raw bits), so dup_handle/1 can open an equivalent one
+    access: u32,
+    options: u32,
+    file_id: Option<u64>, // Some for open_by_id handles
+    // Where the next handle_write goes; kept here since smb's Seek can't move its cursor
+    pos: AtomicU64,
+}
+
+// Cancellation flag for a running stream_lines/4; set by cancel/1 from any process.
+struct CancelToken {
+    cancelled: AtomicBool,
+}
+
+// Cumulative data transfer of one Conn; updated by the NIFs that move file content.
+#[derive(Default)]
+struct IoCounters {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    ops_read: AtomicU64,
+    ops_written: AtomicU64,
+    wait_us: AtomicU64, // wall time spent in those transfers
+}
+
+impl IoCounters {
+    fn record_read(&self, bytes: usize, started: Instant) {
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.ops_read.fetch_add(1, Ordering::Relaxed);
+        self.add_wait(started);
+    }
+
+    fn record_write(&self, bytes: usize, started: Instant) {
+        self.bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.ops_written.fetch_add(1, Ordering::Relaxed);
+        self.add_wait(started);
+    }
+
+    fn add_wait(&self, started: Instant) {
+        let us = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+        self.wait_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> IoStats {
+        IoStats {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            ops_read: self.ops_read.load(Ordering::Relaxed),
+            ops_written: self.ops_written.load(Ordering::Relaxed),
+            wait_us: self.wait_us.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for c in [&self.bytes_read, &self.bytes_written, &self.ops_read, &self.ops_written, &self.wait_us] {
+            c.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// Options of connect_with_opts; missing keys keep the connect/3 defaults.
+#[derive(Clone)]
+struct ConnectOpts {
+    channels: usize,
+    initial_credits: Option<u16>,
+    max_credits: Option<u16>,
+    idle_timeout: Option<Duration>,
+    client_name: Option<String>, // None: derived from the OS hostname
+    kerberos: bool,              // connect_kerberos: Kerberos only, NTLM disabled
+    handle_cache: usize,         // read handles kept open per Conn; 0 = off
+    max_read_bytes: Option<u64>, // whole-file reads above this are :too_large; None = no cap
+    // Per-request timeout in ms, 0 = none. Shared by all clones of these opts, so a
+    // set_timeout/2 also reaches the clients built later (idle reconnects).
+    timeout_ms: Arc<AtomicU64>,
+}
+
+impl Default for ConnectOpts {
+    fn default() -> Self {
+        ConnectOpts {
+            channels: 1,
+            initial_credits: None,
+            max_credits: None,
+            idle_timeout: None,
+            client_name: None,
+            kerberos: false,
+            handle_cache: 0,
+            max_read_bytes: None,
+            timeout_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+// Authenticated (IPC$) session to a server, not yet bound to any share.
+// Credentials are kept for the per-share tree connects.
+struct Session {
+    client: Arc<Mutex<Client>>,
+    server: String, // host[:port]
+    username: String,
+    password: Zeroizing<String>,
+    opts: ConnectOpts, // what the client was built with; reconnects build the same
+    owner: LocalPid, // the authenticating process; told about lock recoveries
+}
+
+impl Session {
+    // Locks the client, recovering a poisoned lock like Conn::lock_recovering; the
+    // session is checked by listing the shares and set up again if that fails.
+    fn lock_client(&self) -> NifResult<MutexGuard<'_, Client>> {
+        lock_recovering(
+            &self.client,
+            &self.owner,
+            &self.server,
+            |c| c.list_shares(&self.server).is_ok(),
+            || {
+                let mut fresh = Client::new(client_config(&self.opts));
+                fresh
+                    .ipc_connect(&self.server, &self.username, self.password.as_str().to_owned())
+                    .map_err(|e| connect_error("reconnect_error", &e))?;
+                Ok(fresh)
+            },
+        )
+    }
+}
+
+// A FILETIME in unix nanoseconds, keeping apart the two special values: 0 ("unknown",
+// encoded as nil) and all-ones ("do not change", encoded as :no_change).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum UnixNanos {
+    Unknown,
+    NoChange,
+    At(u64),
+}
+
+impl Encoder for UnixNanos {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            UnixNanos::Unknown => rustler::types::atom::nil().encode(env),
+            UnixNanos::NoChange => atoms::no_change().encode(env),
+            UnixNanos::At(ns) => ns.encode(env),
+        }
+    }
+}
+
+impl<'a> rustler::Decoder<'a> for UnixNanos {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if let Ok(ns) = term.decode::<u64>() {
+            return Ok(UnixNanos::At(ns));
+        }
+        match term.decode::<Atom>()? {
+            a if a == rustler::types::atom::nil() => Ok(UnixNanos::Unknown),
+            a if a == atoms::no_change() => Ok(UnixNanos::NoChange),
+            _ => Err(rustler::Error::BadArg),
+        }
+    }
 }
 
 #[derive(NifMap)]
 struct RichStats {
-    r#type: Atom,            // :file | :directory
+    r#type: Atom,            // :file | :directory | :symlink
     size: u64,               // EndOfFile
     allocation_size: u64,    // AllocationSize
     nlink: u32,              // NumberOfLinks
@@ -52,264 +468,3787 @@ struct RichStats {
     atime: u64,              // LastAccessTime -> unix seconds
     ctime: u64,              // ChangeTime -> unix seconds
     btime: u64,              // CreationTime -> unix seconds
+    mtime_ns: UnixNanos,     // same four in unix ns; nil = unknown, :no_change = sentinel
+    atime_ns: UnixNanos,
+    ctime_ns: UnixNanos,
+    btime_ns: UnixNanos,
+}
+
+#[derive(NifMap)]
+struct AttrStats {
+    r#type: Atom,            // :file | :directory | :symlink
+    attributes: u32,         // FILE_ATTRIBUTE_* bitmask (LE)
+    mtime: u64,              // LastWriteTime -> unix seconds
+    atime: u64,              // LastAccessTime -> unix seconds
+    ctime: u64,              // ChangeTime -> unix seconds
+    btime: u64,              // CreationTime -> unix seconds
+    mtime_ns: UnixNanos,     // same four in unix ns; nil = unknown, :no_change = sentinel
+    atime_ns: UnixNanos,
+    ctime_ns: UnixNanos,
+    btime_ns: UnixNanos,
+}
+
+// What open/4 tells about the handle it opened (the CREATE response fields the smb
+// client keeps, or queried right after).
+#[derive(NifMap)]
+struct CreateInfo {
+    create_action: Atom,     // :superseded | :opened | :created | :overwritten
+    end_of_file: u64,
+    attributes: u32,         // FILE_ATTRIBUTE_* bitmask (LE)
+    granted_access: u32,     // effective access mask, generic rights mapped to file rights
+}
+
+// read_file_info/2: what the content cost, next to the content itself
+#[derive(NifMap)]
+struct ReadInfo {
+    size: u64,                    // EndOfFile: logical bytes returned
+    allocation_size: u64,         // AllocationSize: below `size` for sparse files
+    compressed_size: Option<u64>, // FileCompressionInformation; nil if not answered
+}
+
+// probe/2: what an unauthenticated NEGOTIATE tells about a server
+#[derive(NifMap)]
+struct ProbeInfo {
+    guid: String,
+    dialect: String,                   // highest dialect both sides speak, e.g. "Smb0311"
+    signing_required: bool,            // SMB2_NEGOTIATE_SIGNING_REQUIRED
+    encryption: bool,                  // SMB2_GLOBAL_CAP_ENCRYPTION (supported)
+    encryption_required: Option<bool>, // decided at session setup; always nil here
+    dfs: bool,
+    leasing: bool,
+    large_mtu: bool,
+    multichannel: bool,
+    persistent_handles: bool,
+    directory_leasing: bool,
+    max_read_size: u32,
+    max_write_size: u32,
+    max_transact_size: u32,
+}
+
+#[derive(NifMap)]
+struct IoStats {
+    bytes_read: u64,
+    bytes_written: u64,
+    ops_read: u64,
+    ops_written: u64,
+    wait_us: u64, // total time spent transferring, microseconds
+}
+
+#[derive(NifMap)]
+struct ServerInfo {
+    guid: String,              // server GUID from NEGOTIATE
+    dialect: String,           // negotiated dialect, e.g. "Smb0311"
+    dfs: bool,                 // SMB2_GLOBAL_CAP_DFS
+    leasing: bool,             // SMB2_GLOBAL_CAP_LEASING
+    large_mtu: bool,           // SMB2_GLOBAL_CAP_LARGE_MTU
+    multichannel: bool,        // SMB2_GLOBAL_CAP_MULTI_CHANNEL
+    persistent_handles: bool,  // SMB2_GLOBAL_CAP_PERSISTENT_HANDLES
+    directory_leasing: bool,   // SMB2_GLOBAL_CAP_DIRECTORY_LEASING
+    encryption: bool,          // SMB2_GLOBAL_CAP_ENCRYPTION
+    max_read_size: u32,
+    max_write_size: u32,
+    max_transact_size: u32,
+    max_credits: u16,          // credit window this Conn's client asks the server for
 }
 
 mod atoms {
-    rustler::atoms! { ok, error, file, directory, not_found}
+    rustler::atoms! { ok, error, file, directory, not_found, channels, initial_credits, max_credits,
+        idle_timeout_ms, connected, logged_off, disabled, exists, closed,
+        sharing_violation, unsupported, symlink, client_name,
+        username, nt_hash, domain, bad_hash, kerberos_unavailable, keytab, ccache,
+        partial, no_change, eof, more, transport, direct_tcp,
+        transport_unsupported, superseded, opened, created, overwritten,
+        share_not_found, auth_failed, too_large, handle_cache, delete_pending,
+        line, cancelled, not_a_file, not_a_directory, overwrite, create_new, open_if,
+        supersede, ntstatus, timeout_ms, path_not_covered, entry, walk_error,
+        target_exists, append, cancel, is_a_directory, not_smb, offline, no_space,
+        access_denied, bad_sid, bad_class, id_full, both, full, names_only,
+        read, write, read_write, max_read_bytes, appended, rotated, cycle_detected,
+        pipe, rumbex_lock_recovered, kept, reconnected }
 }
 
 // SMB/NTSTATUS — most needed
 const STATUS_OBJECT_NAME_NOT_FOUND: u32 = 0xC0000034;
+const STATUS_OBJECT_PATH_NOT_FOUND: u32 = 0xC000003A;
 const STATUS_DELETE_PENDING:       u32 = 0xC0000056;
 const STATUS_DIRECTORY_NOT_EMPTY:  u32 = 0xC0000101;
+const STATUS_NO_MORE_FILES:        u32 = 0x80000006;
+const STATUS_NO_SUCH_FILE:         u32 = 0xC000000F; // wildcard matched nothing
+const STATUS_INVALID_OWNER:        u32 = 0xC000005A; // owner not assignable by caller
+const STATUS_MEDIA_WRITE_PROTECTED: u32 = 0xC00000A2; // read-only share or volume
+const STATUS_STOPPED_ON_SYMLINK:   u32 = 0x8000002D;
+
+const IO_REPARSE_TAG_MOUNT_POINT:  u32 = 0xA0000003; // junctions and volume mount points
+const IO_REPARSE_TAG_SYMLINK:      u32 = 0xA000000C;
+const STATUS_OBJECT_NAME_COLLISION: u32 = 0xC0000035;
+const STATUS_SHARING_VIOLATION:    u32 = 0xC0000043;
+const STATUS_NOT_SUPPORTED:        u32 = 0xC00000BB;
+const STATUS_INVALID_PARAMETER:    u32 = 0xC000000D;
+const STATUS_INVALID_DEVICE_REQUEST: u32 = 0xC0000010;
+const STATUS_INVALID_INFO_CLASS:   u32 = 0xC0000003;
+const STATUS_PATH_NOT_COVERED:     u32 = 0xC0000257;
+const STATUS_FILE_IS_A_DIRECTORY:  u32 = 0xC00000BA;
+const STATUS_NOT_A_DIRECTORY:      u32 = 0xC0000103;
+const STATUS_DISK_FULL:            u32 = 0xC000007F;
+const STATUS_ACCESS_DENIED:        u32 = 0xC0000022;
+const STATUS_BAD_NETWORK_NAME:     u32 = 0xC00000CC;
+const STATUS_LOGON_FAILURE:        u32 = 0xC000006D;
+
+// Upper bound for the credit options (Windows servers never grant more than this)
+const MAX_CREDITS: u16 = 8192;
+// The credit window the smb client asks for when no credit option is set
+const DEFAULT_CREDITS: u16 = 128;
+const MAX_CACHED_HANDLES: usize = 64;
+// `rm` with `wait`: how many times to re-probe a pending delete, and the pause between
+const DELETE_CONFIRM_ATTEMPTS: u32 = 20;
+const DELETE_CONFIRM_INTERVAL: Duration = Duration::from_millis(50);
+// stream_lines/4 read size
+const LINE_CHUNK_BYTES: usize = 64 * 1024;
+// Lines read ahead of stream_lines' sender thread before the reader waits for it.
+const LINE_QUEUE_LEN: usize = 256;
+// walk_parallel: at most this many sessions enumerate at once
+const MAX_WALK_WORKERS: usize = 16;
+// probe/2: TCP connect and NEGOTIATE each get this long
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+// how often a pending cancellable connect checks its token
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+// sspi takes a password this many bytes longer than a hex NT hash as the hash itself
+const NT_HASH_PADDING_BYTES: usize = 512;
+// `rm_rf` recursion limit, a backstop behind its visited-directory check
+const MAX_TREE_DEPTH: usize = 64;
+
+// download_file: size of one READ, and how many may be outstanding at most
+const DOWNLOAD_CHUNK_BYTES: usize = 1024 * 1024;
+const MAX_INFLIGHT: usize = 64;
+
+// Largest READ/WRITE sent at once; positional transfers are split into blocks of this
+const IO_BLOCK_BYTES: usize = 1024 * 1024;
+
+// Transfers smaller than this are not worth striping across channels
+const STRIPE_MIN_BYTES: usize = 8 * 1024 * 1024;
  
 // ==================== Helpers ====================
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum Kind { File, Dir }
+enum Kind { File, Dir, Symlink }
 
 fn open_for_kind(client: &mut smb::Client, unc: &UncPath) -> Option<Kind> {
-    let access = FileAccessMask::new().with_generic_read(true);
+    // Attributes only: enough to classify, valid on files and directories alike.
+    // make_open_existing leaves every create option clear, so the open is neutral
+    // (neither directory_file nor non_directory_file) and the returned resource
+    // tells which one it is.
+    let access = FileAccessMask::new().with_file_read_attributes(true);
     let mut args = FileCreateArgs::make_open_existing(access);
+    match smb::client::Client::create_file(client, unc, &args) {
+        Err(e) if retry_as_directory(ntstatus_from_err_display(&e)) => {
+            // some servers refuse the neutral open of a directory outright; an
+            // explicit directory open is the second (and last) try
+            args.options = CreateOptions::default().with_directory_file(true);
+            kind_of_open(smb::client::Client::create_file(client, unc, &args))
+        }
+        opened => kind_of_open(opened),
+    }
+}
 
-    // Try as file
-    args.options = CreateOptions::default(); // by default "not directory"
-    if let Ok(res) = smb::client::Client::create_file(client, unc, &args) {
-        let out = match res {
-            smb::resource::Resource::File(_)      => Some(Kind::File),
-            smb::resource::Resource::Directory(_) => Some(Kind::Dir),
-            _ => None,
-        };
-        drop(res);
-        return out;
+// A neutral open refused for a reason that neither names the kind nor says the path
+// is missing may be a server that won't open directories that way.
+fn retry_as_directory(status: Option<u32>) -> bool {
+    match status {
+        Some(STATUS_OBJECT_NAME_NOT_FOUND | STATUS_OBJECT_PATH_NOT_FOUND) => false,
+        Some(status) => kind_of_status(status).is_none(),
+        None => false,
     }
-    None
 }
 
-fn ntstatus_from_err_display<E: std::fmt::Display>(e: &E) -> Option<u32> {
-    let s = e.to_string();
-    let start = s.find("(0x")?;
-    let hex   = &s[start+3..].trim_end_matches(')');
-    u32::from_str_radix(hex, 16).ok()
+// Create failures that still tell what the path is.
+fn kind_of_status(status: u32) -> Option<Kind> {
+    match status {
+        // server-side symlinks are not followed by the server — the open stops on them
+        STATUS_STOPPED_ON_SYMLINK => Some(Kind::Symlink),
+        STATUS_FILE_IS_A_DIRECTORY => Some(Kind::Dir),
+        STATUS_NOT_A_DIRECTORY => Some(Kind::File),
+        _ => None,
+    }
 }
 
-// FILETIME (100ns ticks since 1601-01-01) -> Unix seconds (>=0; 0 if unknown)
-fn filetime_to_unix_seconds(ticks: u64) -> u64 {
-    if ticks == 0 { return 0; }
-    // 10_000_000 ticks = 1 second; delta between 1601-01-01 and 1970-01-01:
-    const EPOCH_DELTA: u64 = 11_644_473_600;
-    let secs = ticks / 10_000_000;
-    secs.saturating_sub(EPOCH_DELTA)
+// The share root classified by the same rules as any other path: whatever the server
+// answers for the root open, not an assumed directory.
+fn root_kind(client: &mut smb::Client, share: &UncPath) -> Option<Kind> {
+    let access = FileAccessMask::new().with_file_read_attributes(true);
+    kind_of_open(open_share_root(client, share, access))
 }
-    
-// ==================== NIFs ====================
-#[rustler::nif(schedule = "DirtyIo")]
-fn connect<'a>(
-    env: Env<'a>,
-    unc_share: String,
-    username: String,
-    password: String,
-) -> NifResult<Term<'a>> {
-    // expect string like "\\\\host\\share"
-    let share = UncPath::from_str(&unc_share)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("bad_unc: {e}"))))?;
 
-    let mut client = Client::new(ClientConfig::default());
-    client
-        .share_connect(&share, &username, password)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("connect_error: {e}"))))?;
+fn kind_of_open(opened: Result<Resource, smb::Error>) -> Option<Kind> {
+    match opened {
+        Ok(res) => {
+            let out = match res {
+                smb::resource::Resource::File(_)      => Some(Kind::File),
+                smb::resource::Resource::Directory(_) => Some(Kind::Dir),
+                _ => None,
+            };
+            drop(res);
+            out
+        }
+        Err(e) => ntstatus_from_err_display(&e).and_then(kind_of_status),
+    }
+}
 
-    let res = ResourceArc::new(Conn {
-        client: Mutex::new(client),
-        share,
-    });
+// "", "/", "\\" and any other run of separators all name the share root
+fn is_share_root(path_in_share: &str) -> bool {
+    path_in_share.trim_matches(['\\', '/']).is_empty()
+}
 
-    Ok((atoms::ok(), res).encode(env))
+// The share root, always opened as a directory. Every query aimed at the root ("", "/"
+// or "\\" as the path) goes through here, so they all see it the same way.
+fn open_share_root(
+    client: &mut smb::Client,
+    share: &UncPath,
+    access: FileAccessMask,
+) -> Result<Resource, smb::Error> {
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::default().with_directory_file(true);
+    client.create_file(share, &args)
 }
 
-#[rustler::nif(schedule = "DirtyIo")]
-fn read_file<'a>(
-    env: Env<'a>,
-    conn: ResourceArc<Conn>,
-    path_in_share: String,
-) -> NifResult<Term<'a>> {
-    let rel = path_in_share.trim_start_matches(['\\', '/']);
-    let base = conn.share.to_string();
-    let full = if rel.is_empty() { base } else { format!(r"{}\{}", base.trim_end_matches('\\'), rel) };
+// Re-open the path until the server reports it gone. Other handles still open on the
+// file keep it in delete-pending state; we give up after a bounded wait.
+fn wait_for_delete(client: &mut smb::Client, unc: &UncPath) -> NifResult<()> {
+    let access = FileAccessMask::new().with_file_read_attributes(true);
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::default().with_open_reparse_point(true);
 
-    let file_unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    for attempt in 0..DELETE_CONFIRM_ATTEMPTS {
+        if attempt > 0 {
+            std::thread::sleep(DELETE_CONFIRM_INTERVAL);
+        }
+        match smb::client::Client::create_file(client, unc, &args) {
+            Ok(res) => drop(res),
+            Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+                return Ok(());
+            }
+            Err(_) => {} // STATUS_DELETE_PENDING and friends: not gone yet
+        }
+    }
+    Err(rustler::Error::Term(Box::new(atoms::delete_pending())))
+}
 
-    let mut client = conn.client.lock().map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
-    let access = FileAccessMask::new().with_generic_read(true);
-    let args = FileCreateArgs::make_open_existing(access);
+// What `rm_rf` needs to know about one entry, read from the entry itself (never
+// from what a link points to).
+struct TreeEntry {
+    dir: bool,
+    reparse_tag: Option<u32>,
+    offline: bool, // FILE_ATTRIBUTE_OFFLINE: data tiered away (HSM)
+    dir_id: u64,   // directories: the file system's file id (0 if it has none)
+}
 
-    let resource: Resource = client
-        .create_file(&file_unc, &args)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("smb_open_failed: {e}"))))?;
+// `None` if the path does not exist.
+fn probe_entry(conn: &Conn, unc: &UncPath) -> NifResult<Option<TreeEntry>> {
+    let access = FileAccessMask::new().with_file_read_attributes(true);
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::default().with_open_reparse_point(true);
 
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(unc, &args) {
+        Ok(res) => res,
+        Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+            conn.note_status(&e);
+            return Ok(None);
+        }
+        Err(e) => return Err(conn.create_error("smb_open_failed", &e)),
+    };
     drop(client);
 
-    let mut file: SmbFile = resource
-        .try_into()
-        .map_err(|_| rustler::Error::Term(Box::new("not_a_file")))?;
+    let tag_err = |e: smb::Error| conn.op_error("query_attribute_tag_failed", &e);
+    let (dir, info, dir_id): (bool, FileAttributeTagInformation, u64) = match res {
+        Resource::File(file) => (false, file.query_info().map_err(tag_err)?, 0),
+        Resource::Directory(dir) => {
+            // FAT and some NAS file systems have no stable ids and report 0
+            let id: Option<FileInternalInformation> = dir.query_info().ok();
+            (true, dir.query_info().map_err(tag_err)?, id.map_or(0, |i| i.index_number))
+        }
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
 
-    let mut buf = Vec::new();
-    file.read_to_end(&mut buf)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("smb_read_failed: {e}"))))?;
+    let attributes = FileAttributes::from_bytes(info.file_attributes.to_le_bytes());
+    Ok(Some(TreeEntry {
+        dir,
+        reparse_tag: attributes.reparse_point().then_some(info.reparse_tag),
+        offline: attributes.offline(),
+        dir_id,
+    }))
+}
 
-    let mut obin = OwnedBinary::new(buf.len())
-        .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
-    obin.as_mut_slice().copy_from_slice(&buf);
-    let bin_term = obin.release(env);
+// How rm_tree deletes the entries of one directory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ChildDelete {
+    // The parent is held open with FILE_DELETE_CHILD, which the server checked once:
+    // each child is opened for DELETE (granted through the parent's delete-child right
+    // even where the child's own ACL has no DELETE) and marked with
+    // FileDispositionInformation.
+    Disposition,
+    // The parent refused FILE_DELETE_CHILD: a delete-on-close open per child, each one
+    // access-checked on its own.
+    OnClose,
+}
 
-    Ok((atoms::ok(), bin_term).encode(env))
+// Opens the directory `unc` with FILE_DELETE_CHILD; `None` where the server or the
+// caller's rights don't allow it, and the children are deleted one by one instead.
+fn open_for_delete_child(conn: &Conn, unc: &UncPath, entry: &TreeEntry) -> Option<Resource> {
+    let mut args =
+        FileCreateArgs::make_open_existing(FileAccessMask::new().with_file_delete_child(true));
+    args.options = CreateOptions::default()
+        .with_directory_file(true)
+        .with_open_reparse_point(entry.reparse_tag.is_some());
+    let mut client = conn.lock_client().ok()?;
+    client.create_file(unc, &args).ok()
 }
 
-#[rustler::nif(schedule = "DirtyIo")]
-fn write_file<'a>(
-    env: Env<'a>,
-    conn: ResourceArc<Conn>,
-    path_in_share: String,
-    data: Binary<'a>,
-) -> NifResult<Term<'a>> {
-    let rel = path_in_share.trim_start_matches(['\\', '/']);
-    let base = conn.share.to_string();
-    let full = if rel.is_empty() { base } else { format!(r"{}\{}", base.trim_end_matches('\\'), rel) };
+// The DELETE open of a single entry; a reparse point is opened (and removed) as the
+// link itself. Only the delete-on-close flag differs between the two modes.
+fn delete_args(entry: &TreeEntry, mode: ChildDelete) -> FileCreateArgs {
+    let mut args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true));
+    let opts = CreateOptions::default()
+        .with_delete_on_close(mode == ChildDelete::OnClose)
+        .with_open_reparse_point(entry.reparse_tag.is_some());
+    args.options = if entry.dir {
+        opts.with_directory_file(true)
+    } else {
+        opts.with_non_directory_file(true)
+    };
+    args
+}
 
-    let file_unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+// Deletes a single entry. A server that won't take the disposition (the set-info
+// fails for any reason but a non-empty directory) gets a delete-on-close open instead.
+fn delete_entry(conn: &Conn, unc: &UncPath, entry: &TreeEntry, mode: ChildDelete) -> NifResult<()> {
+    let not_empty = || rustler::Error::Term(Box::new("dir_not_empty"));
+    let mut client = conn.lock_client()?;
+    let handle = match client.create_file(unc, &delete_args(entry, mode)) {
+        Ok(handle) => handle,
+        Err(e) => {
+            return match ntstatus_from_err_display(&e) {
+                Some(STATUS_OBJECT_NAME_NOT_FOUND) | Some(STATUS_DELETE_PENDING) => Ok(()),
+                Some(STATUS_DIRECTORY_NOT_EMPTY) => Err(not_empty()),
+                _ => Err(conn.op_error("rm_failed", &e)),
+            };
+        }
+    };
+    drop(client);
+    if mode == ChildDelete::OnClose {
+        drop(handle);
+        return Ok(());
+    }
 
-    let mut client = conn.client.lock().map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let pending = FileDispositionInformation { delete_pending: Boolean::from(true) };
+    let marked = match &handle {
+        Resource::File(file) => file.set_file_info(pending),
+        Resource::Directory(dir) => dir.set_file_info(pending),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+    drop(handle);
+    match marked {
+        Ok(()) => Ok(()),
+        Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_DIRECTORY_NOT_EMPTY) => {
+            Err(not_empty())
+        }
+        Err(_) => delete_entry(conn, unc, entry, ChildDelete::OnClose),
+    }
+}
 
-    // overwrite/create with RW access
-    let mut args = FileCreateArgs::make_overwrite(FileAttributes::default(), CreateOptions::default());
-    args.desired_access = FileAccessMask::new().with_generic_read(true).with_generic_write(true);
+// rm_rf's switches
+struct RmOpts {
+    follow: bool,
+    dry_run: bool,
+    skip_offline: bool,
+}
 
-    let resource: Resource = client
-        .create_file(&file_unc, &args)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("smb_create_failed: {e}"))))?;
+// rm_rf's findings: what was (or, dry run, would be) deleted, and the offline files
+// left alone
+#[derive(Default)]
+struct RmOut {
+    paths: Vec<String>,
+    offline: Vec<String>,
+}
 
-    drop(client);
+// Post-order walk: children first, then the entry. Links are only descended into with
+// `follow`, and SMB symlinks never (the server won't resolve them for us), so without
+// `follow` nothing outside `rel` is touched. With `dry_run` paths are collected in `out`.
+// With `skip_offline` offline files are kept, and so are the directories above them;
+// returns whether `rel` is kept. `seen` holds the file ids of the directories descended
+// into so far: a junction leading back into the tree is removed as a link, not walked
+// again. `mode` is how `rel` itself is deleted, as decided for its parent's entries.
+fn rm_tree(
+    conn: &Conn,
+    rel: &str,
+    mode: ChildDelete,
+    opts: &RmOpts,
+    depth: usize,
+    seen: &mut HashSet<u64>,
+    out: &mut RmOut,
+) -> NifResult<bool> {
+    let unc = unc_in_share(&conn.share, rel)?;
+    let Some(entry) = probe_entry(conn, &unc)? else {
+        return Ok(false);
+    };
+    if opts.skip_offline && entry.offline && !entry.dir {
+        out.offline.push(rel.to_string());
+        return Ok(true);
+    }
 
-    let mut file: SmbFile = resource
-        .try_into()
-        .map_err(|_| rustler::Error::Term(Box::new("not_a_file")))?;
+    let descend = match entry.reparse_tag {
+        None => entry.dir,
+        Some(IO_REPARSE_TAG_SYMLINK) => false,
+        Some(_) => entry.dir && opts.follow,
+    } && (entry.dir_id == 0 || seen.insert(entry.dir_id));
+    let mut kept = false;
+    if descend {
+        if depth >= MAX_TREE_DEPTH {
+            return Err(rustler::Error::Term(Box::new("too_deep")));
+        }
+        let (children, _skipped, partial) = collect_dir_entries(conn, rel)?;
+        if let Some(reason) = partial {
+            return Err(rustler::Error::Term(Box::new(format!("list_failed: {reason}"))));
+        }
+        // held until the children are gone; dropping it closes the parent handle
+        let parent = (!opts.dry_run)
+            .then(|| open_for_delete_child(conn, &unc, &entry))
+            .flatten();
+        let child_mode = match parent {
+            Some(_) => ChildDelete::Disposition,
+            None => ChildDelete::OnClose,
+        };
+        for (name, _kind) in children {
+            let child = format!("{rel}/{name}");
+            kept |= rm_tree(conn, &child, child_mode, opts, depth + 1, seen, out)?;
+        }
+        drop(parent);
+    }
+    if kept {
+        return Ok(true);
+    }
 
-    file.write_all(data.as_slice())
-        .map_err(|e| rustler::Error::Term(Box::new(format!("smb_write_failed: {e}"))))?;
+    if opts.dry_run {
+        out.paths.push(rel.to_string());
+        return Ok(false);
+    }
+    conn.forget_handle(rel);
+    delete_entry(conn, &unc, &entry, mode)?;
+    Ok(false)
+}
 
-    Ok(atoms::ok().encode(env))
+// smb errors spell the status as "<description> (0xc0000034)." or, for codes it has no
+// name for, "status: 0x12345678."; the first `0x` followed by 1-8 hex digits is it.
+fn ntstatus_from_err_display<E: std::fmt::Display>(e: &E) -> Option<u32> {
+    let s = e.to_string();
+    s.match_indices("0x").find_map(|(i, _)| {
+        let digits = &s[i + 2..];
+        let len = digits.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(digits.len());
+        (1..=8).contains(&len).then(|| u32::from_str_radix(&digits[..len], 16).ok()).flatten()
+    })
 }
 
-#[rustler::nif(schedule = "DirtyIo")]
-fn list_dir<'a>(
-    env: Env<'a>,
-    conn: ResourceArc<Conn>,
-    path_in_share: String,
-) -> NifResult<Term<'a>> {
-    // relative path inside share
-    let rel = path_in_share.trim_matches(['\\', '/']);
-    let base = conn.share.to_string();
+// CREATE failures callers commonly branch on become atoms; anything else stays
+// a "<what>: <smb error>" string.
+fn create_error<E: std::fmt::Display>(what: &str, e: &E) -> rustler::Error {
+    match ntstatus_from_err_display(e) {
+        Some(STATUS_SHARING_VIOLATION) => rustler::Error::Term(Box::new(atoms::sharing_violation())),
+        // DFS link whose referral could not be followed to a reachable target
+        Some(STATUS_PATH_NOT_COVERED) => rustler::Error::Term(Box::new(atoms::path_not_covered())),
+        // a non-directory open (write_file, open_writer) of an existing directory
+        Some(STATUS_FILE_IS_A_DIRECTORY) => rustler::Error::Term(Box::new(atoms::is_a_directory())),
+        // and the other way round: a directory-only open (listings) of a file
+        Some(STATUS_NOT_A_DIRECTORY) => rustler::Error::Term(Box::new(atoms::not_a_directory())),
+        // no room left even for the new directory entry
+        Some(STATUS_DISK_FULL) => rustler::Error::Term(Box::new(atoms::no_space())),
+        _ => rustler::Error::Term(Box::new(format!("{what}: {e}"))),
+    }
+}
+
+// Write-family failures: a full disk becomes `:no_space`, so callers can pause instead
+// of retrying; anything else stays a "<what>: <smb error>" string.
+fn write_error<E: std::fmt::Display>(what: &str, e: &E) -> rustler::Error {
+    match ntstatus_from_err_display(e) {
+        Some(STATUS_DISK_FULL) => rustler::Error::Term(Box::new(atoms::no_space())),
+        _ => rustler::Error::Term(Box::new(format!("{what}: {e}"))),
+    }
+}
+
+// Session setup / tree connect failures. The two callers branch on (retry vs.
+// re-prompt credentials) become atoms; anything else stays a "what: e" string.
+fn connect_error<E: std::fmt::Display>(what: &str, e: &E) -> rustler::Error {
+    connect_failure(what, e).into()
+}
+
+// Why a connect failed, as plain data: unlike rustler::Error it can cross threads, which
+// the handshake worker of connect_cancellable needs.
+enum ConnectFailure {
+    Atom(Atom),
+    Reason(String),
+}
+
+impl From<ConnectFailure> for rustler::Error {
+    fn from(f: ConnectFailure) -> Self {
+        match f {
+            ConnectFailure::Atom(a) => rustler::Error::Term(Box::new(a)),
+            ConnectFailure::Reason(r) => rustler::Error::Term(Box::new(r)),
+        }
+    }
+}
+
+fn connect_failure<E: std::fmt::Display>(what: &str, e: &E) -> ConnectFailure {
+    match ntstatus_from_err_display(e) {
+        Some(STATUS_BAD_NETWORK_NAME) => ConnectFailure::Atom(atoms::share_not_found()),
+        Some(STATUS_LOGON_FAILURE) => ConnectFailure::Atom(atoms::auth_failed()),
+        _ => ConnectFailure::Reason(format!("{what}: {e}")),
+    }
+}
+
+// A whole-file read of `size` bytes against `cap` (None = no cap): past the cap it is
+// `{:too_large, size}`, decided before anything is allocated or read.
+fn check_read_cap(size: u64, cap: Option<u64>) -> NifResult<()> {
+    if exceeds_read_cap(size, cap) {
+        return Err(rustler::Error::Term(Box::new((atoms::too_large(), size))));
+    }
+    Ok(())
+}
+
+fn exceeds_read_cap(size: u64, cap: Option<u64>) -> bool {
+    cap.is_some_and(|cap| size > cap)
+}
+
+// FILETIME sentinel for "don't change this timestamp" (SET_INFO); some servers echo it.
+const FILETIME_NO_CHANGE: u64 = u64::MAX;
+// FILETIMEs are signed on the wire; anything past i64::MAX (year 30828) is corrupt
+// and clamped there rather than wrapping in the conversions below.
+const FILETIME_MAX: u64 = i64::MAX as u64;
+
+// FILETIME (100ns ticks since 1601-01-01) -> Unix seconds (>=0; 0 if unknown or no-change)
+fn filetime_to_unix_seconds(ticks: u64) -> u64 {
+    if ticks == 0 || ticks == FILETIME_NO_CHANGE { return 0; }
+    // 10_000_000 ticks = 1 second; delta between 1601-01-01 and 1970-01-01:
+    const EPOCH_DELTA: u64 = 11_644_473_600;
+    let secs = ticks.min(FILETIME_MAX) / 10_000_000;
+    secs.saturating_sub(EPOCH_DELTA)
+}
+
+// FILETIME -> Unix nanoseconds, keeping the full 100ns resolution. Times before 1970
+// clamp to 0; after 2554 (past u64 ns) to u64::MAX.
+fn filetime_to_unix_nanos(ticks: u64) -> UnixNanos {
+    const EPOCH_DELTA_TICKS: u64 = 11_644_473_600 * 10_000_000;
+    match ticks {
+        0 => UnixNanos::Unknown,
+        FILETIME_NO_CHANGE => UnixNanos::NoChange,
+        t => {
+            let t = t.min(FILETIME_MAX);
+            UnixNanos::At(t.saturating_sub(EPOCH_DELTA_TICKS).saturating_mul(100))
+        }
+    }
+}
+
+// Unix nanoseconds -> FILETIME for SET_INFO: nil (Unknown) is 0, which leaves the
+// timestamp alone; :no_change is the all-ones "stop updating it" sentinel.
+fn unix_nanos_to_filetime(t: UnixNanos) -> u64 {
+    const EPOCH_DELTA_TICKS: u64 = 11_644_473_600 * 10_000_000;
+    match t {
+        UnixNanos::Unknown => 0,
+        UnixNanos::NoChange => FILETIME_NO_CHANGE,
+        UnixNanos::At(ns) => (ns / 100).saturating_add(EPOCH_DELTA_TICKS),
+    }
+}
+
+// "\\\\host\\share" -> "host"
+fn share_server(share: &UncPath) -> String {
+    share
+        .to_string()
+        .trim_start_matches('\\')
+        .split('\\')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Names come back as UTF-16 and may legally contain unpaired surrogates; decode
+// strictly (None) instead of lossily, so a name we hand out always round-trips
+// through SizedWideString on the next open/rename.
+fn wide_name(name: &SizedWideString) -> Option<String> {
+    String::from_utf16(name).ok()
+}
+
+// FileBasicInformation + FileStandardInformation of an open file or directory
+// (both support query_info via Deref<ResourceHandle>)
+fn rich_stats_of(conn: &Conn, res: Resource) -> NifResult<RichStats> {
+    let basic_err = |e: smb::Error| conn.op_error("query_basic_failed", &e);
+    let std_err = |e: smb::Error| conn.op_error("query_standard_failed", &e);
+
+    let (kind, basic, stdi): (Atom, FileBasicInformation, FileStandardInformation) = match res {
+        Resource::File(file) => (
+            atoms::file(),
+            file.query_info().map_err(basic_err)?,
+            file.query_info().map_err(std_err)?,
+        ),
+        Resource::Directory(dir) => (
+            atoms::directory(),
+            dir.query_info().map_err(basic_err)?,
+            dir.query_info().map_err(std_err)?,
+        ),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+
+    Ok(RichStats {
+        r#type: kind,
+        size: stdi.end_of_file,
+        allocation_size: stdi.allocation_size,
+        nlink: stdi.number_of_links,
+        attributes: u32::from_le_bytes(basic.file_attributes.into_bytes()),
+        mtime: filetime_to_unix_seconds(*basic.last_write_time),
+        atime: filetime_to_unix_seconds(*basic.last_access_time),
+        ctime: filetime_to_unix_seconds(*basic.change_time),
+        btime: filetime_to_unix_seconds(*basic.creation_time),
+        mtime_ns: filetime_to_unix_nanos(*basic.last_write_time),
+        atime_ns: filetime_to_unix_nanos(*basic.last_access_time),
+        ctime_ns: filetime_to_unix_nanos(*basic.change_time),
+        btime_ns: filetime_to_unix_nanos(*basic.creation_time),
+    })
+}
+
+// SMB2 CREATE with FILE_OPEN_BY_FILE_ID sends the 8-byte file id as the name buffer.
+// The client only takes UNC strings (sent as UTF-16LE), so the id is spelled as the
+// four UTF-16 units with the same bytes. Ids whose units aren't valid, separator-free
+// chars can't be expressed that way (None).
+fn file_id_unc(share: &UncPath, file_id: u64) -> Option<UncPath> {
+    let bytes = file_id.to_le_bytes();
+    let mut name = String::with_capacity(8);
+    for pair in bytes.chunks_exact(2) {
+        let unit = u16::from_le_bytes([pair[0], pair[1]]);
+        match char::from_u32(u32::from(unit)) {
+            Some(c) if c != '\0' && c != '\\' && c != '/' => name.push(c),
+            _ => return None,
+        }
+    }
+    UncPath::from_str(&format!(r"{}\{}", share.to_string().trim_end_matches('\\'), name)).ok()
+}
+
+// Opens a resource by its file id (see file_id_unc); servers without support for
+// FILE_OPEN_BY_FILE_ID on this share answer NOT_SUPPORTED / INVALID_PARAMETER.
+fn open_by_file_id(conn: &Conn, file_id: u64, access: FileAccessMask) -> NifResult<Resource> {
+    let unc = file_id_unc(&conn.share, file_id)
+        .ok_or_else(|| rustler::Error::Term(Box::new(atoms::unsupported())))?;
+
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::default().with_open_by_file_id(true);
+
+    let mut client = conn.lock_client()?;
+    client.create_file(&unc, &args).map_err(|e| match ntstatus_from_err_display(&e) {
+        Some(STATUS_NOT_SUPPORTED) | Some(STATUS_INVALID_PARAMETER) => {
+            rustler::Error::Term(Box::new(atoms::unsupported()))
+        }
+        Some(STATUS_OBJECT_NAME_NOT_FOUND) => rustler::Error::Term(Box::new(atoms::not_found())),
+        _ => conn.create_error("smb_open_failed", &e),
+    })
+}
+
+// Access a successful open of `res` with `desired` carries. The server either grants
+// every requested right or fails the open, so that is `desired` with the generic rights
+// mapped to their file rights; MAXIMUM_ALLOWED is replaced by the maximal access the
+// server reported for the caller (which may be less than asked for).
+fn granted_access_of(res: &Resource, desired: u32) -> u32 {
+    let mut granted = desired
+        & !(ACCESS_GENERIC_READ | ACCESS_GENERIC_WRITE | ACCESS_GENERIC_EXECUTE)
+        & !(ACCESS_GENERIC_ALL | ACCESS_MAXIMUM_ALLOWED);
+    for (generic, mapped) in [
+        (ACCESS_GENERIC_READ, FILE_GENERIC_READ),
+        (ACCESS_GENERIC_WRITE, FILE_GENERIC_WRITE),
+        (ACCESS_GENERIC_EXECUTE, FILE_GENERIC_EXECUTE),
+        (ACCESS_GENERIC_ALL, FILE_ALL_ACCESS),
+    ] {
+        if desired & generic != 0 {
+            granted |= mapped;
+        }
+    }
+    if desired & ACCESS_MAXIMUM_ALLOWED != 0 {
+        granted |= match res {
+            Resource::File(file) => u32::from_le_bytes(file.access().into_bytes()),
+            // the smb client keeps the maximal access of files only; a directory
+            // opened with MAXIMUM_ALLOWED holds exactly that, so ask the handle
+            Resource::Directory(dir) => dir
+                .query_info::<FileAccessInformation>()
+                .map_or(0, |info| u32::from_le_bytes(info.access_flags.into_bytes())),
+            _ => 0,
+        };
+    }
+    granted
+}
+
+// CreateInfo of `res`, opened with access mask `desired` and CREATE action `action`.
+// The smb client drops the rest of the CREATE response (the FileId with it), so size
+// and attributes come from one FileNetworkOpenInformation query; pipes answer 0 for
+// both.
+fn create_info_of(res: &Resource, desired: u32, action: Atom) -> smb::Result<CreateInfo> {
+    let (end_of_file, attributes) = match res {
+        Resource::File(file) => network_open_of(file.query_info()?),
+        Resource::Directory(dir) => network_open_of(dir.query_info()?),
+        Resource::Pipe(_) => (0, 0),
+    };
+    Ok(CreateInfo {
+        create_action: action,
+        end_of_file,
+        attributes,
+        granted_access: granted_access_of(res, desired),
+    })
+}
+
+fn network_open_of(info: FileNetworkOpenInformation) -> (u64, u32) {
+    (info.end_of_file, u32::from_le_bytes(info.file_attributes.into_bytes()))
+}
+
+// Handle cache key: SMB names are case-insensitive, separators either way.
+fn cache_key(rel: &str) -> String {
+    rel.trim_matches(['\\', '/']).replace('/', "\\").to_lowercase()
+}
+
+// UNC of a share as callers pass it to connect: "\\host\share", or the URL-style
+// "//host/share" (forward slashes normalized before parsing).
+fn parse_share(unc_share: &str) -> NifResult<UncPath> {
+    UncPath::from_str(&unc_share.replace('/', "\\"))
+        .map_err(|e| rustler::Error::Term(Box::new(format!("bad_unc: {e}"))))
+}
+
+// share-relative path -> full UNC ("" or "/" -> share root)
+fn unc_in_share(share: &UncPath, path_in_share: &str) -> NifResult<UncPath> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    let base = share.to_string();
     let full = if rel.is_empty() {
         base
     } else {
         format!(r"{}\{}", base.trim_end_matches('\\'), rel)
     };
-    let dir_unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)
+}
 
-    // open directory descriptor
-    let mut client = conn
-        .client
-        .lock()
-        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
-    let access = FileAccessMask::new().with_generic_read(true);
-    let args = FileCreateArgs::make_open_existing(access);
+// "dir/name" -> "dir\.name.tmp.<unique>" — hidden sibling in the same directory,
+// so the final rename never crosses directories.
+fn temp_sibling(rel: &str) -> String {
+    static SEQ: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let tag = nanos ^ (SEQ.fetch_add(1, Ordering::Relaxed) << 48) ^ u64::from(std::process::id());
+
+    let rel = rel.replace('/', "\\");
+    match rel.rsplit_once('\\') {
+        Some((dir, name)) => format!(r"{dir}\.{name}.tmp.{tag:x}"),
+        None => format!(".{rel}.tmp.{tag:x}"),
+    }
+}
 
+// Access for a directory that is only enumerated: FILE_LIST_DIRECTORY (same bit as
+// FILE_READ_DATA) | FILE_READ_ATTRIBUTES, all that QUERY_DIRECTORY needs. generic_read
+// also asks for EA/security reads, which tight ACLs deny even where Explorer can still
+// browse.
+fn list_dir_access() -> FileAccessMask {
+    FileAccessMask::new()
+        .with_file_read_data(true)
+        .with_file_read_attributes(true)
+        .with_synchronize(true)
+}
+
+// Opens a share-relative directory for enumeration. A file there is refused by the server
+// (STATUS_NOT_A_DIRECTORY -> :not_a_directory) instead of failing later on QUERY_DIRECTORY.
+fn open_listed_dir(conn: &Conn, path_in_share: &str) -> NifResult<Directory> {
+    let dir_unc = unc_in_share(&conn.share, path_in_share)?;
+
+    let mut client = conn.lock_client()?;
+    let mut args = FileCreateArgs::make_open_existing(list_dir_access());
+    args.options = CreateOptions::default().with_directory_file(true);
     let res: Resource = client
         .create_file(&dir_unc, &args)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("smb_open_failed: {e}"))))?;
+        .map_err(|e| conn.create_error("smb_open_failed", &e))?;
+    drop(client);
 
-    drop(client); // client no longer needed
+    res.try_into()
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::not_a_directory())))
+}
 
-    // convert to Directory
-    let dir: Directory = res
-        .try_into()
-        .map_err(|_| rustler::Error::Term(Box::new("not_a_directory")))?;
+// Hands `each` every record of an enumeration whose name decodes, "." and ".." left out.
+// Names that aren't valid UTF-16 (a lossy one could never be reopened) and corrupted
+// records (errors without a status) are skipped and counted. NO_MORE_FILES, or
+// NO_SUCH_FILE for a pattern nothing matches, ends it; any other status means a whole
+// QUERY_DIRECTORY round failed, and the iterator won't resume after that, so the error
+// is returned next to the skip count.
+fn walk_records<T, E, I, N, F>(records: I, name_of: N, mut each: F) -> (u64, Option<E>)
+where
+    E: std::fmt::Display,
+    I: IntoIterator<Item = Result<T, E>>,
+    N: Fn(&T) -> &SizedWideString,
+    F: FnMut(String, &T),
+{
+    let mut skipped: u64 = 0;
+    for item in records {
+        match item {
+            Ok(record) => match wide_name(name_of(&record)) {
+                None => skipped += 1,
+                Some(name) if name == "." || name == ".." => {}
+                Some(name) => each(name, &record),
+            },
+            Err(e) => match ntstatus_from_err_display(&e) {
+                Some(STATUS_NO_MORE_FILES) | Some(STATUS_NO_SUCH_FILE) => break,
+                Some(_) => return (skipped, Some(e)),
+                None => skipped += 1,
+            },
+        }
+    }
+    (skipped, None)
+}
 
-    // read list, use class without short_name
+// Records skipped and — if enumeration broke off midway — why.
+type Walked = (u64, Option<String>);
+
+// Enumerates a share-relative directory as `T` (`*` when `pattern` is empty), feeding
+// each entry to `each`; see walk_records for what is skipped and where it stops.
+fn enumerate_dir<T, N, F>(
+    conn: &Conn,
+    path_in_share: &str,
+    pattern: &str,
+    name_of: N,
+    each: F,
+) -> NifResult<Walked>
+where
+    T: QueryDirectoryInfoValue,
+    N: Fn(&T) -> &SizedWideString,
+    F: FnMut(String, &T),
+{
+    let dir = open_listed_dir(conn, path_in_share)?;
+    let pattern = if pattern.is_empty() { "*" } else { pattern };
     let iter = dir
-        .query_directory::<FileIdFullDirectoryInformation>("*")
-        .map_err(|e| rustler::Error::Term(Box::new(format!("query_failed: {e}"))))?;
+        .query_directory::<T>(pattern)
+        .map_err(|e| conn.op_error("query_failed", &e))?;
+
+    let (skipped, failed) = walk_records(iter, name_of, each);
+    let broke_off = failed.map(|e| {
+        conn.note_status(&e);
+        format!("query_failed: {e}")
+    });
+    Ok((skipped, broke_off))
+}
 
+// Entries read so far, records skipped, and — if enumeration broke off midway — why.
+type DirListing = (Vec<(String, Atom)>, u64, Option<String>);
+
+// Opens a share-relative directory and enumerates it (without "." / "..").
+// Returns the entries and the number of corrupted/undecodable records that were skipped.
+fn collect_dir_entries(conn: &Conn, path_in_share: &str) -> NifResult<DirListing> {
     let mut out: Vec<(String, Atom)> = Vec::new();
+    // class without short_name: some servers are slow to compute 8.3 names, so list_dir
+    // must never ask for them (list_dir_class picks explicitly)
+    let (skipped, broke_off) = enumerate_dir(
+        conn,
+        path_in_share,
+        "*",
+        |info: &FileIdFullDirectoryInformation| &info.file_name,
+        |name, info| out.push((name, kind_of_attrs(&info.file_attributes))),
+    )?;
+    Ok((out, skipped, broke_off))
+}
 
-    for item in iter {
-        match item {
-            Ok(info) => {
-                let name = info.file_name.to_string();
-                if name == "." || name == ".." {
-                    continue;
+// :directory or :file, from a directory record's attributes
+fn kind_of_attrs(attrs: &FileAttributes) -> Atom {
+    if attrs.directory() { atoms::directory() } else { atoms::file() }
+}
+
+// Tree-connects `share` on `client`. smb's share_connect sets up a TCP connection and
+// session of its own for every share (authenticating with these credentials again),
+// whatever sessions the client already holds.
+fn tree_connect_share(
+    client: &mut Client,
+    share: &UncPath,
+    username: &str,
+    password: &str,
+) -> Result<(), ConnectFailure> {
+    client
+        .share_connect(share, username, password.to_string())
+        .map_err(|e| connect_failure("connect_error", &e))
+}
+
+// A Conn with default options on `client`, already tree-connected to `share`.
+fn new_conn(
+    client: Arc<Mutex<Client>>,
+    share: UncPath,
+    username: &str,
+    password: &str,
+    owner: LocalPid,
+) -> Conn {
+    Conn {
+        client,
+        share,
+        channels: Vec::new(),
+        username: username.to_string(),
+        password: Zeroizing::new(password.to_string()),
+        opts: ConnectOpts::default(),
+        idle: None,
+        last_status: AtomicU32::new(0),
+        io: IoCounters::default(),
+        handles: Mutex::new(Vec::new()),
+        write_protected: AtomicBool::new(false),
+        owner,
+    }
+}
+
+fn decode_connect_opts(opts: Term) -> NifResult<ConnectOpts> {
+    let mut out = ConnectOpts::default();
+    if let Ok(v) = opts.map_get(atoms::channels()) {
+        out.channels = v.decode::<usize>()?.clamp(1, 16);
+    }
+    if let Ok(v) = opts.map_get(atoms::initial_credits()) {
+        out.initial_credits = Some(v.decode::<u16>()?.clamp(1, MAX_CREDITS));
+    }
+    if let Ok(v) = opts.map_get(atoms::max_credits()) {
+        out.max_credits = Some(v.decode::<u16>()?.clamp(1, MAX_CREDITS));
+    }
+    if let Ok(v) = opts.map_get(atoms::idle_timeout_ms()) {
+        match v.decode::<u64>()? {
+            0 => out.idle_timeout = None,
+            ms => out.idle_timeout = Some(Duration::from_millis(ms)),
+        }
+    }
+    if let Ok(v) = opts.map_get(atoms::client_name()) {
+        let name = v.decode::<String>()?;
+        if !valid_client_name(&name) {
+            return Err(rustler::Error::Term(Box::new(
+                "bad_client_name: 1..15 chars of A-Z, a-z, 0-9, '-' or '_'",
+            )));
+        }
+        out.client_name = Some(name);
+    }
+    if let Ok(v) = opts.map_get(atoms::handle_cache()) {
+        out.handle_cache = v.decode::<usize>()?.min(MAX_CACHED_HANDLES);
+    }
+    if let Ok(v) = opts.map_get(atoms::max_read_bytes()) {
+        out.max_read_bytes = match v.decode::<u64>()? {
+            0 => None,
+            n => Some(n),
+        };
+    }
+    if let Ok(v) = opts.map_get(atoms::timeout_ms()) {
+        out.timeout_ms = Arc::new(AtomicU64::new(v.decode::<u64>()?));
+    }
+    // direct TCP is the only transport the smb crate has; :netbios (port 139) waits for it
+    if let Ok(v) = opts.map_get(atoms::transport()) {
+        if v.decode::<Atom>()? != atoms::direct_tcp() {
+            return Err(rustler::Error::Term(Box::new(atoms::transport_unsupported())));
+        }
+    }
+    if let (Some(initial), Some(max)) = (out.initial_credits, out.max_credits) {
+        if initial > max {
+            return Err(rustler::Error::Term(Box::new("bad_credits: initial_credits > max_credits")));
+        }
+    }
+    Ok(out)
+}
+
+fn client_config(opts: &ConnectOpts) -> ClientConfig {
+    let mut cfg = ClientConfig {
+        // Follow DFS referrals: a CREATE answered with STATUS_PATH_NOT_COVERED (a subpath
+        // hosted on another server) is resolved through a referral and retried there.
+        dfs: true,
+        ..Default::default()
+    };
+    // The client asks for `credits_backlog` extra credits on every request, which is what
+    // grows the window; asking for no more than max_credits keeps the grant under it.
+    // The server still has the last word and may grant less.
+    let backlog = match (opts.initial_credits, opts.max_credits) {
+        (Some(initial), _) => Some(initial),
+        (None, Some(max)) => Some(max),
+        (None, None) => None,
+    };
+    if let Some(n) = backlog {
+        cfg.connection.credits_backlog = Some(n.min(opts.max_credits.unwrap_or(MAX_CREDITS)));
+    }
+    // a request not answered in time fails with a timeout instead of blocking forever
+    match opts.timeout_ms.load(Ordering::Relaxed) {
+        0 => {}
+        ms => cfg.connection.timeout = Some(Duration::from_millis(ms)),
+    }
+    // workstation name sent during session setup; some NASes refuse unnamed clients
+    cfg.connection.client_name = opts.client_name.clone().or_else(os_client_name);
+    #[cfg(feature = "kerberos")]
+    if opts.kerberos {
+        cfg.connection.auth_methods.ntlm = false;
+        cfg.connection.auth_methods.kerberos = true;
+    }
+    cfg
+}
+
+// NetBIOS-compatible: at most 15 chars, letters/digits/'-'/'_'.
+fn valid_client_name(name: &str) -> bool {
+    (1..=15).contains(&name.len())
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+// First label of the OS hostname, cut to 15 chars; None if there is no usable one.
+fn os_client_name() -> Option<String> {
+    let host = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())?;
+    let name: String = host.trim().split('.').next()?.chars().take(15).collect();
+    valid_client_name(&name).then_some(name)
+}
+
+// Asked with a NEGOTIATE of its own (see negotiate_raw); no answer counts as no.
+fn advertises_multichannel(share: &UncPath) -> bool {
+    negotiate_host(&share_server(share), 445, PROBE_TIMEOUT)
+        .map(|neg| neg.capabilities.multi_channel())
+        .unwrap_or(false)
+}
+
+// connect/3 + connect_with_opts/4: tree connect, then open extra channels if asked for
+// and the server advertises multichannel (otherwise silently stay single-channel).
+// With a `cancel` token, it is checked between the handshakes (main connection, then
+// each extra channel), so a cancelled connect stops at the next one.
+fn connect_with(
+    share: UncPath,
+    username: &str,
+    password: &str,
+    opts: &ConnectOpts,
+    cancel: Option<&CancelToken>,
+    owner: LocalPid,
+) -> Result<Conn, ConnectFailure> {
+    let check_cancel = || match cancel {
+        Some(token) if token.cancelled.load(Ordering::Relaxed) => {
+            Err(ConnectFailure::Atom(atoms::cancelled()))
+        }
+        _ => Ok(()),
+    };
+    let mut client = Client::new(client_config(opts));
+    tree_connect_share(&mut client, &share, username, password)?;
+    let mut conn = new_conn(Arc::new(Mutex::new(client)), share, username, password, owner);
+
+    if opts.channels > 1 && advertises_multichannel(&conn.share) {
+        for _ in 1..opts.channels {
+            check_cancel()?;
+            // every channel is its own TCP connection + session to the same share
+            let mut extra = Client::new(client_config(opts));
+            tree_connect_share(&mut extra, &conn.share, username, password)?;
+            conn.channels.push(Arc::new(Mutex::new(extra)));
+        }
+    }
+    check_cancel()?;
+
+    conn.opts = opts.clone();
+    if let Some(timeout) = opts.idle_timeout {
+        let idle = Arc::new(IdleState {
+            timeout,
+            last_used: Mutex::new(Instant::now()),
+            logged_off: AtomicBool::new(false),
+        });
+        spawn_idle_watcher(&conn, idle.clone());
+        conn.idle = Some(idle);
+    }
+
+    Ok(conn)
+}
+
+// `cancel` key of the connect opts: a token from cancel_token/0. Not part of
+// ConnectOpts — it only guards this one connect, never the reconnects after it.
+fn decode_cancel(opts: Term) -> NifResult<Option<ResourceArc<CancelToken>>> {
+    match opts.map_get(atoms::cancel()) {
+        Ok(v) => Ok(Some(v.decode()?)),
+        Err(_) => Ok(None),
+    }
+}
+
+// EINPROGRESS (Linux 115, BSD/macOS 36); Windows reports WSAEWOULDBLOCK as WouldBlock.
+fn connect_pending(e: &std::io::Error) -> bool {
+    let einprogress = if cfg!(target_os = "linux") { 115 } else { 36 };
+    e.kind() == std::io::ErrorKind::WouldBlock || e.raw_os_error() == Some(einprogress)
+}
+
+// Non-blocking TCP connect to the share's server that gives up as soon as `cancel` is
+// set, dropping (and so closing) the half-open socket. The smb client's own connect
+// can't be interrupted, so it only runs once this one got through; the socket is
+// closed again right away. Bounded by `timeout_ms` when set.
+fn probe_connect(share: &UncPath, opts: &ConnectOpts, cancel: &CancelToken) -> NifResult<()> {
+    let cancelled = || rustler::Error::Term(Box::new(atoms::cancelled()));
+    let addrs: Vec<SocketAddr> = (share_server(share).as_str(), 445)
+        .to_socket_addrs()
+        .map_err(|e| connect_error("connect_error", &e))?
+        .collect();
+    let deadline = match opts.timeout_ms.load(Ordering::Relaxed) {
+        0 => None,
+        ms => Some(Instant::now() + Duration::from_millis(ms)),
+    };
+
+    let mut last_err = std::io::Error::new(std::io::ErrorKind::NotFound, "no address");
+    for addr in addrs {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))
+            .and_then(|s| s.set_nonblocking(true).map(|_| s))
+            .map_err(|e| connect_error("connect_error", &e))?;
+        match socket.connect(&addr.into()) {
+            Ok(()) => return Ok(()),
+            Err(e) if connect_pending(&e) => {}
+            Err(e) => {
+                last_err = e;
+                continue;
+            }
+        }
+        loop {
+            if cancel.cancelled.load(Ordering::Relaxed) {
+                return Err(cancelled());
+            }
+            match socket.take_error() {
+                Ok(None) => {}
+                Ok(Some(e)) | Err(e) => {
+                    last_err = e;
+                    break;
                 }
-                let kind = if info.file_attributes.directory() {
-                    atoms::directory()
-                } else {
-                    atoms::file()
-                };
-                out.push((name, kind));
             }
-            Err(_e) => {
-                // sometimes corrupted records are encountered — just skip them
-                continue;
+            if socket.peer_addr().is_ok() {
+                return Ok(());
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                last_err = std::io::ErrorKind::TimedOut.into();
+                break;
             }
+            std::thread::sleep(CONNECT_POLL_INTERVAL);
         }
     }
+    Err(connect_error("connect_error", &last_err))
+}
 
-    Ok((atoms::ok(), out).encode(env))
+// connect_with behind probe_connect when a cancel token was given. The smb handshakes
+// can't be interrupted, so they run on a worker this call walks away from once the
+// token is set: the caller gets `:cancelled` right away, and the worker stops at its
+// next step and drops whatever it had set up (closing its sockets).
+fn connect_cancellable(
+    share: UncPath,
+    username: &str,
+    password: &str,
+    opts: &ConnectOpts,
+    cancel: Option<&ResourceArc<CancelToken>>,
+    owner: LocalPid,
+) -> NifResult<Conn> {
+    let Some(cancel) = cancel else {
+        return Ok(connect_with(share, username, password, opts, None, owner)?);
+    };
+    let cancelled = || rustler::Error::Term(Box::new(atoms::cancelled()));
+    probe_connect(&share, opts, cancel)?;
+
+    let (tx, rx) = mpsc::channel();
+    let (username, password) = (username.to_string(), Zeroizing::new(password.to_string()));
+    let (opts, token) = (opts.clone(), cancel.clone());
+    std::thread::spawn(move || {
+        // a send error means the caller gave up: the Conn is dropped here
+        let _ = tx.send(connect_with(share, &username, &password, &opts, Some(&token), owner));
+    });
+
+    loop {
+        if cancel.cancelled.load(Ordering::Relaxed) {
+            return Err(cancelled());
+        }
+        match rx.recv_timeout(CONNECT_POLL_INTERVAL) {
+            Ok(conn) => return Ok(conn?),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(rustler::Error::Term(Box::new("connect_panicked")));
+            }
+        }
+    }
+}
+
+// Logs the Conn's clients off once it has been unused for `idle.timeout`. Replacing a
+// client drops its session (the socket closes as soon as no open handle uses it);
+// `Conn::lock_client` reconnects on next use. Exits when the Conn is dropped.
+fn spawn_idle_watcher(conn: &Conn, idle: Arc<IdleState>) {
+    let client: Weak<Mutex<Client>> = Arc::downgrade(&conn.client);
+    let channels: Vec<Weak<Mutex<Client>>> = conn.channels.iter().map(Arc::downgrade).collect();
+    let opts = conn.opts.clone();
+    let tick = (idle.timeout / 4).clamp(Duration::from_millis(50), Duration::from_secs(5));
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(tick);
+
+        let Some(client) = client.upgrade() else { return };
+        if idle.logged_off.load(Ordering::Acquire) || idle.idle_for() < idle.timeout {
+            continue;
+        }
+        // in use right now — not idle
+        let Ok(mut guard) = client.try_lock() else { continue };
+
+        *guard = Client::new(client_config(&opts));
+        for ch in channels.iter().filter_map(Weak::upgrade) {
+            if let Ok(mut ch) = ch.lock() {
+                *ch = Client::new(client_config(&opts));
+            }
+        }
+        idle.logged_off.store(true, Ordering::Release);
+    });
+}
+
+// Access mask bits no file open can carry: undefined specific rights (9..=15),
+// reserved standard rights (21..=23) and reserved bits 26..=27.
+const ACCESS_RESERVED_BITS:  u32 = 0x0000_FE00 | 0x00E0_0000 | 0x0C00_0000;
+const ACCESS_DELETE:         u32 = 0x0001_0000;
+const ACCESS_WRITE_DATA:     u32 = 0x0000_0002;
+// FILE_WRITE_DATA / FILE_APPEND_DATA as they read on a directory
+const ACCESS_ADD_FILE:       u32 = 0x0000_0002;
+const ACCESS_ADD_SUBDIRECTORY: u32 = 0x0000_0004;
+const ACCESS_GENERIC_WRITE:  u32 = 0x4000_0000;
+const ACCESS_GENERIC_READ:   u32 = 0x8000_0000;
+const ACCESS_GENERIC_EXECUTE: u32 = 0x2000_0000;
+const ACCESS_GENERIC_ALL:    u32 = 0x1000_0000;
+const ACCESS_MAXIMUM_ALLOWED: u32 = 0x0200_0000;
+// What the generic rights map to for files (MS-SMB2 / winnt.h FILE_GENERIC_*)
+const FILE_GENERIC_READ:     u32 = 0x0012_0089;
+const FILE_GENERIC_WRITE:    u32 = 0x0012_0116;
+const FILE_GENERIC_EXECUTE:  u32 = 0x0012_00A0;
+const FILE_ALL_ACCESS:       u32 = 0x001F_01FF;
+// CreateOptions values end at FILE_OPEN_FOR_FREE_SPACE_QUERY (0x0080_0000).
+const OPTIONS_RESERVED_BITS: u32 = 0xFF00_0000;
+const OPT_DIRECTORY_FILE:     u32 = 0x0000_0001;
+const OPT_NON_DIRECTORY_FILE: u32 = 0x0000_0040;
+const OPT_DELETE_ON_CLOSE:    u32 = 0x0000_1000;
+// FILE_ATTRIBUTE_* a file may be created with: READONLY, HIDDEN, SYSTEM, ARCHIVE,
+// NORMAL, TEMPORARY, OFFLINE and NOT_CONTENT_INDEXED (no DIRECTORY, no reparse point)
+const CREATE_ATTRIBUTES:      u32 = 0x0000_31A7;
+
+// Rejects raw open/4 masks the server would refuse anyway (or silently misread).
+fn validate_open_bits(access: u32, options: u32) -> Result<(), &'static str> {
+    if access == 0 || access & ACCESS_RESERVED_BITS != 0 {
+        return Err("bad_access");
+    }
+    if options & OPTIONS_RESERVED_BITS != 0 {
+        return Err("bad_options");
+    }
+    if options & OPT_DIRECTORY_FILE != 0 && options & OPT_NON_DIRECTORY_FILE != 0 {
+        return Err("bad_options");
+    }
+    if options & OPT_DELETE_ON_CLOSE != 0 && access & ACCESS_DELETE == 0 {
+        return Err("bad_options");
+    }
+    Ok(())
+}
+
+// Positional reads and writes on an open file. smb's `Seek` for File never moves the
+// cursor its `Read`/`Write` use, so anything but one sequential pass from offset 0 has to
+// name its offset in the READ/WRITE itself.
+trait BlockIo {
+    // One READ of at most `buf.len()` bytes at `pos`; 0 at end of file.
+    fn read_block_at(&self, buf: &mut [u8], pos: u64) -> std::io::Result<usize>;
+    // One WRITE of `data` at `pos`; the byte count the server took.
+    fn write_block_at(&self, data: &[u8], pos: u64) -> std::io::Result<usize>;
+}
+
+impl BlockIo for SmbFile {
+    fn read_block_at(&self, buf: &mut [u8], pos: u64) -> std::io::Result<usize> {
+        self.read_block(buf, pos, false)
+    }
+
+    fn write_block_at(&self, data: &[u8], pos: u64) -> std::io::Result<usize> {
+        self.write_block(data, pos)
+    }
+}
+
+// Reads from `pos` until `buf` is full or the file ends, IO_BLOCK_BYTES per READ;
+// returns how much was read.
+fn read_up_to_at(file: &impl BlockIo, buf: &mut [u8], pos: u64) -> std::io::Result<usize> {
+    let mut done = 0;
+    while done < buf.len() {
+        let end = buf.len().min(done + IO_BLOCK_BYTES);
+        let n = file.read_block_at(&mut buf[done..end], pos + done as u64)?;
+        if n == 0 {
+            break;
+        }
+        done += n;
+    }
+    Ok(done)
+}
+
+// Fills all of `buf` from `pos`; UnexpectedEof if the file ends first.
+fn read_exact_at(file: &impl BlockIo, buf: &mut [u8], pos: u64) -> std::io::Result<()> {
+    let n = read_up_to_at(file, buf, pos)?;
+    if n < buf.len() {
+        let why = format!("end of file after {n} of {} bytes", buf.len());
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, why));
+    }
+    Ok(())
+}
+
+// Writes all of `data` at `pos`, IO_BLOCK_BYTES per WRITE.
+fn write_all_at(file: &impl BlockIo, data: &[u8], pos: u64) -> std::io::Result<()> {
+    let mut done = 0;
+    while done < data.len() {
+        let end = data.len().min(done + IO_BLOCK_BYTES);
+        let n = file.write_block_at(&data[done..end], pos + done as u64)?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+// Opens existing `unc` on one channel, for a stripe of striped_read/striped_write.
+fn open_on_channel(
+    conn: &Conn,
+    channel: &Mutex<Client>,
+    unc: &UncPath,
+    access: FileAccessMask,
+) -> Result<SmbFile, String> {
+    let mut client = conn.lock_recovering(channel).map_err(|_| "mutex_poisoned".to_string())?;
+    let args = FileCreateArgs::make_open_existing(access);
+    let res = client.create_file(unc, &args).map_err(|e| format!("smb_open_failed: {e}"))?;
+    drop(client);
+    res.try_into().map_err(|_| "not_a_file".to_string())
+}
+
+// Splits `buf` into `parts` stripes and reads them concurrently: the first through
+// `main` on this thread, stripe i+1 through `open(i)` on a thread of its own.
+fn read_stripes<H, O>(buf: &mut [u8], parts: usize, main: &H, open: O) -> Result<(), String>
+where
+    H: BlockIo,
+    O: Fn(usize) -> Result<H, String> + Sync,
+{
+    let stripe = buf.len().div_ceil(parts).max(1);
+    let mut chunks = buf.chunks_mut(stripe);
+    let first = chunks.next().unwrap_or_default();
+    let open = &open;
+
+    std::thread::scope(|sc| {
+        let workers: Vec<_> = chunks
+            .enumerate()
+            .map(|(i, chunk)| {
+                let offset = ((i + 1) * stripe) as u64;
+                sc.spawn(move || {
+                    let file = open(i)?;
+                    read_exact_at(&file, chunk, offset).map_err(|e| format!("smb_read_failed: {e}"))
+                })
+            })
+            .collect();
+
+        read_exact_at(main, first, 0).map_err(|e| format!("smb_read_failed: {e}"))?;
+
+        for w in workers {
+            w.join().map_err(|_| "stripe_panicked".to_string())??;
+        }
+        Ok(())
+    })
+}
+
+// Same as read_stripes, for writes.
+fn write_stripes<H, O>(data: &[u8], parts: usize, main: &H, open: O) -> Result<(), String>
+where
+    H: BlockIo,
+    O: Fn(usize) -> Result<H, String> + Sync,
+{
+    let stripe = data.len().div_ceil(parts).max(1);
+    let mut chunks = data.chunks(stripe);
+    let first = chunks.next().unwrap_or_default();
+    let open = &open;
+
+    std::thread::scope(|sc| {
+        let workers: Vec<_> = chunks
+            .enumerate()
+            .map(|(i, chunk)| {
+                let offset = ((i + 1) * stripe) as u64;
+                sc.spawn(move || {
+                    let file = open(i)?;
+                    write_all_at(&file, chunk, offset).map_err(|e| format!("smb_write_failed: {e}"))
+                })
+            })
+            .collect();
+
+        write_all_at(main, first, 0).map_err(|e| format!("smb_write_failed: {e}"))?;
+
+        for w in workers {
+            w.join().map_err(|_| "stripe_panicked".to_string())??;
+        }
+        Ok(())
+    })
+}
+
+// Reads `buf` from offset 0 in one stripe per channel (+ the main handle), concurrently.
+fn striped_read(conn: &Conn, unc: &UncPath, file: &SmbFile, buf: &mut [u8]) -> Result<(), String> {
+    let access = FileAccessMask::new().with_generic_read(true);
+    read_stripes(buf, conn.channels.len() + 1, file, |i| {
+        open_on_channel(conn, &conn.channels[i], unc, access)
+    })
+}
+
+// download_file worker: takes the next chunk until none are left, reads it through its
+// own handle and writes it at the same offset of `local`. Stops once `failed` is set.
+fn download_chunks(
+    file: &SmbFile,
+    size: u64,
+    next: &AtomicU64,
+    failed: &AtomicBool,
+    local: &Mutex<std::fs::File>,
+) -> Result<(), String> {
+    let mut buf = Vec::new();
+    while !failed.load(Ordering::Relaxed) {
+        let offset = next.fetch_add(DOWNLOAD_CHUNK_BYTES as u64, Ordering::Relaxed);
+        if offset >= size {
+            break;
+        }
+        let len = (size - offset).min(DOWNLOAD_CHUNK_BYTES as u64) as usize;
+        buf.resize(len, 0);
+        read_exact_at(file, &mut buf, offset).map_err(|e| format!("smb_read_failed: {e}"))?;
+
+        let mut local = local.lock().map_err(|_| "mutex_poisoned".to_string())?;
+        local
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| local.write_all(&buf))
+            .map_err(|e| format!("local_write_failed: {e}"))?;
+    }
+    Ok(())
 }
 
+// Same as striped_read, for writes; `file` must already be created/truncated.
+fn striped_write(conn: &Conn, unc: &UncPath, file: &SmbFile, data: &[u8]) -> Result<(), String> {
+    let access = FileAccessMask::new().with_generic_write(true);
+    write_stripes(data, conn.channels.len() + 1, file, |i| {
+        open_on_channel(conn, &conn.channels[i], unc, access)
+    })
+}
+
+// ==================== NIFs ====================
 #[rustler::nif(schedule = "DirtyIo")]
-fn stat<'a>(
+fn connect<'a>(
     env: Env<'a>,
-    conn: ResourceArc<Conn>,
-    path_in_share: String,
+    unc_share: String,
+    username: String,
+    password: String,
 ) -> NifResult<Term<'a>> {
-    // relative path inside share (without leading \ or /)
-    let rel = path_in_share.trim_start_matches(['\\', '/']);
+    // scrubbed on every return path; copies handed to the smb client are its own
+    let password = Zeroizing::new(password);
+    // "\\\\host\\share", or "//host/share"
+    let share = parse_share(&unc_share)?;
 
-    // build full UNC: "\\host\share\rel"
-    let base = conn.share.to_string();
-    let full = if rel.is_empty() {
-        base
-    } else {
-        format!(r"{}\{}", base.trim_end_matches('\\'), rel)
-    };
+    // authenticate + tree connect in one go (share_connect sets up the session itself)
+    let conn = connect_with(share, &username, &password, &ConnectOpts::default(), None, env.pid())?;
+    let res = ResourceArc::new(conn);
 
-    let unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    Ok((atoms::ok(), res).encode(env))
+}
+
+/// `connect/3` with an options map:
+/// - `channels` — number of connections to open to the share (default 1, max 16).
+///   Only honored when the server advertises multichannel; large `read_file` /
+///   `write_file` transfers are then striped across all of them.
+/// - `initial_credits` / `max_credits` — SMB2 credit window (1..=8192). Unset keeps
+///   the `smb` crate default; 256..512 is a good start for high-latency links.
+/// - `idle_timeout_ms` — log off and close the socket after this long without use
+///   (0 / unset = never); the next operation reconnects transparently.
+/// - `client_name` — workstation name sent in session setup (NetBIOS-style, 1..15
+///   chars); defaults to the OS hostname.
+/// - `handle_cache` — keep up to n (max 64) read handles open for reuse by
+///   `read_file` on the same path (0 / unset = off). Writes, renames and deletes
+///   through this Conn drop the affected handle; changes by other clients don't.
+/// - `transport` — `:direct_tcp` (port 445, the default and only one supported);
+///   anything else, `:netbios` included, is `:transport_unsupported`.
+/// - `cancel` — a `cancel_token/0`; `cancel/1` on it returns `{:error, :cancelled}`
+///   at once. A TCP connect still in progress is aborted (the socket is closed); an SMB
+///   handshake already under way is abandoned to a worker thread, which stops before
+///   its next step (e.g. the next channel) and drops the connection.
+#[rustler::nif(schedule = "DirtyIo")]
+fn connect_with_opts<'a>(
+    env: Env<'a>,
+    unc_share: String,
+    username: String,
+    password: String,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let password = Zeroizing::new(password);
+    let share = parse_share(&unc_share)?;
+    let cancel = decode_cancel(opts)?;
+    let opts = decode_connect_opts(opts)?;
+
+    let res = ResourceArc::new(connect_cancellable(
+        share,
+        &username,
+        &password,
+        &opts,
+        cancel.as_ref(),
+        env.pid(),
+    )?);
+
+    Ok((atoms::ok(), res).encode(env))
+}
+
+/// Pass-the-hash variant of `connect_with_opts`:
+/// `auth = %{username: u, nt_hash: <<16 bytes>>, domain: d}` (`domain` optional).
+/// A hash that isn't exactly 16 bytes gives `{:error, :bad_hash}`. The NTLM session
+/// setup works from the hash itself; no cleartext password exists anywhere.
+#[rustler::nif(schedule = "DirtyIo")]
+fn connect_with_hash<'a>(
+    env: Env<'a>,
+    unc_share: String,
+    auth: Term<'a>,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let share = parse_share(&unc_share)?;
+    let cancel = decode_cancel(opts)?;
+    let opts = decode_connect_opts(opts)?;
+
+    let username: String = auth.map_get(atoms::username())?.decode()?;
+    let username = match auth.map_get(atoms::domain()) {
+        Ok(d) => format!(r"{}\{}", d.decode::<String>()?, username),
+        Err(_) => username,
+    };
+    let hash: Binary = auth.map_get(atoms::nt_hash())?.decode()?;
+    if hash.len() != 16 {
+        return Err(rustler::Error::Term(Box::new(atoms::bad_hash())));
+    }
+    // kept as the Conn's password, so reconnects authenticate from the hash as well
+    let password = nt_hash_password(&hash);
+
+    let res = ResourceArc::new(connect_cancellable(
+        share,
+        &username,
+        &password,
+        &opts,
+        cancel.as_ref(),
+        env.pid(),
+    )?);
+
+    Ok((atoms::ok(), res).encode(env))
+}
+
+// The password form under which the NTLM provider (sspi) takes an NT hash instead of a
+// password: the hash as hex digits followed by 512 bytes of padding, in the UTF-16
+// buffer the provider reads. sspi reads that buffer byte by byte, so each UTF-16 unit
+// carries two hex digits, low byte first.
+fn nt_hash_password(hash: &[u8]) -> Zeroizing<String> {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let units = hash
+        .iter()
+        .map(|b| u16::from(HEX[usize::from(b >> 4)]) | u16::from(HEX[usize::from(b & 0xF)]) << 8)
+        .chain(std::iter::repeat_n(0, NT_HASH_PADDING_BYTES / 2));
+    // hex digit pairs are 0x3030..=0x4646, never a surrogate
+    Zeroizing::new(char::decode_utf16(units).map(|c| c.unwrap_or('\0')).collect())
+}
+
+/// Kerberos-only variant of `connect_with_opts` (NTLM is not offered). The service
+/// principal is `cifs/<host>` of `unc_share`, so use the name the KDC knows, not an IP.
+/// `opts` takes the `connect_with_opts` keys. The smb client's Kerberos (sspi) gets its
+/// ticket with the principal's password: it reads no keytab or credential cache and
+/// takes no setting for one, so `keytab` / `ccache` are `{:error, :unsupported}`.
+///
+/// `KRB5_CONFIG` (or /etc/krb5.conf) must name the realm and its KDCs. Returns
+/// `{:error, :kerberos_unavailable}` when built without the `kerberos` feature.
+#[rustler::nif(schedule = "DirtyIo")]
+fn connect_kerberos<'a>(
+    env: Env<'a>,
+    unc_share: String,
+    principal: String,
+    password: String,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let password = Zeroizing::new(password);
+    if !cfg!(feature = "kerberos") {
+        return Err(rustler::Error::Term(Box::new(atoms::kerberos_unavailable())));
+    }
+
+    if opts.map_get(atoms::keytab()).is_ok() || opts.map_get(atoms::ccache()).is_ok() {
+        return Err(rustler::Error::Term(Box::new(atoms::unsupported())));
+    }
+
+    let share = parse_share(&unc_share)?;
+    let cancel = decode_cancel(opts)?;
+    let mut conn_opts = decode_connect_opts(opts)?;
+    conn_opts.kerberos = true;
+
+    let res = ResourceArc::new(connect_cancellable(
+        share,
+        &principal,
+        &password,
+        &conn_opts,
+        cancel.as_ref(),
+        env.pid(),
+    )?);
+
+    Ok((atoms::ok(), res).encode(env))
+}
+
+/// Authenticates against `server` (session setup + IPC$ tree connect) without
+/// binding to a share. Use `tree_connect/2` to get a `Conn` for a share later.
+#[rustler::nif(schedule = "DirtyIo")]
+fn authenticate<'a>(
+    env: Env<'a>,
+    server: String,
+    username: String,
+    password: String,
+) -> NifResult<Term<'a>> {
+    let password = Zeroizing::new(password);
+    let server = server.trim_start_matches(['\\', '/']).trim_end_matches(['\\', '/']);
+    if server.is_empty() || server.contains(['\\', '/']) {
+        return Err(rustler::Error::Term(Box::new("bad_server")));
+    }
+
+    let opts = ConnectOpts::default();
+    let mut client = Client::new(client_config(&opts));
+    client
+        .ipc_connect(server, &username, password.as_str().to_owned())
+        .map_err(|e| connect_error("connect_error", &e))?;
+
+    let res = ResourceArc::new(Session {
+        client: Arc::new(Mutex::new(client)),
+        server: server.to_string(),
+        username,
+        password,
+        opts,
+        owner: env.pid(),
+    });
+
+    Ok((atoms::ok(), res).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_shares<'a>(env: Env<'a>, session: ResourceArc<Session>) -> NifResult<Term<'a>> {
+    let mut client = session.lock_client()?;
+
+    let shares = client
+        .list_shares(&session.server)
+        .map_err(|e| rustler::Error::Term(Box::new(format!("list_shares_failed: {e}"))))?;
+
+    // entries without a name (null pointer) are skipped
+    let names: Vec<String> = shares
+        .iter()
+        .filter_map(|sh| match &sh.netname {
+            NdrPtr::Resolved(Some(name)) => Some(name.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    Ok((atoms::ok(), names).encode(env))
+}
+
+/// Tree-connects a share with the credentials of an authenticated `Session`. The smb
+/// client can't reuse the session's SMB session for another share: each tree connect
+/// sets up a connection and session of its own, and the returned `Conn` gets a client
+/// of its own for it.
+#[rustler::nif(schedule = "DirtyIo")]
+fn tree_connect<'a>(
+    env: Env<'a>,
+    session: ResourceArc<Session>,
+    share_name: String,
+) -> NifResult<Term<'a>> {
+    let name = share_name.trim_matches(['\\', '/']);
+    if name.is_empty() || name.contains(['\\', '/']) {
+        return Err(rustler::Error::Term(Box::new("bad_share")));
+    }
+
+    let share = UncPath::from_str(&format!(r"\\{}\{}", session.server, name))
+        .map_err(|e| rustler::Error::Term(Box::new(format!("bad_unc: {e}"))))?;
+
+    // not the session's client: Conn::reconnect (idle logoff, set_timeout) replaces
+    // the whole client, which would take the session's IPC$ and sibling Conns with it
+    let mut client = Client::new(client_config(&session.opts));
+    tree_connect_share(&mut client, &share, &session.username, &session.password)?;
+    let conn = new_conn(
+        Arc::new(Mutex::new(client)),
+        share,
+        &session.username,
+        &session.password,
+        env.pid(),
+    );
+
+    Ok((atoms::ok(), ResourceArc::new(conn)).encode(env))
+}
+
+// A NEGOTIATE of our own over a throwaway TCP connection to `addr`, offering every
+// dialect the client speaks (plus the contexts 3.1.1 requires): the smb client keeps
+// what its connections negotiated private. An answer that isn't an SMB2 NEGOTIATE
+// response is an InvalidData error.
+fn negotiate_raw(addr: &SocketAddr, timeout: Duration) -> std::io::Result<NegotiateResponse> {
+    let bad = |e: &dyn std::fmt::Display| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+    };
+    let mut stream = std::net::TcpStream::connect_timeout(addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut salt = Cursor::new(Vec::new());
+    for _ in 0..2 {
+        Guid::generate().write_le(&mut salt).map_err(|e| bad(&e))?;
+    }
+    let request = NegotiateRequest {
+        security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+        capabilities: GlobalCapabilities::new()
+            .with_dfs(true)
+            .with_leasing(true)
+            .with_large_mtu(true)
+            .with_multi_channel(true)
+            .with_directory_leasing(true)
+            .with_encryption(true),
+        client_guid: Guid::generate(),
+        dialects: Dialect::ALL.to_vec(),
+        negotiate_context_list: Some(vec![
+            NegotiateContextValue::PreauthIntegrityCapabilities(PreauthIntegrityCapabilities {
+                hash_algorithms: vec![HashAlgorithm::Sha512],
+                salt: salt.into_inner(),
+            })
+            .into(),
+            NegotiateContextValue::EncryptionCapabilities(EncryptionCapabilities {
+                ciphers: vec![
+                    EncryptionCipher::Aes128Gcm,
+                    EncryptionCipher::Aes128Ccm,
+                    EncryptionCipher::Aes256Gcm,
+                    EncryptionCipher::Aes256Ccm,
+                ],
+            })
+            .into(),
+        ]),
+    };
+    let mut msg = PlainRequest::new(RequestContent::Negotiate(request));
+    msg.header.message_id = 0;
+    msg.header.credit_request = 1;
+
+    let mut body = Cursor::new(Vec::new());
+    msg.write(&mut body).map_err(|e| bad(&e))?;
+    let body = body.into_inner();
+    let mut frame = Cursor::new(Vec::with_capacity(SmbTcpMessageHeader::SIZE + body.len()));
+    SmbTcpMessageHeader { stream_protocol_length: body.len() as u32 }
+        .write(&mut frame)
+        .map_err(|e| bad(&e))?;
+    let mut frame = frame.into_inner();
+    frame.extend_from_slice(&body);
+    stream.write_all(&frame)?;
+
+    let mut head = [0u8; SmbTcpMessageHeader::SIZE];
+    stream.read_exact(&mut head)?;
+    let head = SmbTcpMessageHeader::read(&mut Cursor::new(&head)).map_err(|e| bad(&e))?;
+    let mut body = vec![0u8; head.stream_protocol_length as usize];
+    stream.read_exact(&mut body)?;
+    let response = PlainResponse::read(&mut Cursor::new(&body)).map_err(|e| bad(&e))?;
+    response.content.to_negotiate().map_err(|e| bad(&e))
+}
+
+// negotiate_raw against the first address of `host` that answers.
+fn negotiate_host(host: &str, port: u16, timeout: Duration) -> std::io::Result<NegotiateResponse> {
+    let mut last_err = std::io::Error::new(std::io::ErrorKind::NotFound, "no address");
+    for addr in (host, port).to_socket_addrs()? {
+        match negotiate_raw(&addr, timeout) {
+            Ok(neg) => return Ok(neg),
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+/// Properties the server behind `conn` negotiates (GUID, dialect, capabilities,
+/// limits) and `max_credits`, the credit window the Conn's client asks for. The smb
+/// client keeps what its own connection negotiated, and the credits the server actually
+/// granted, to itself, so the rest comes from a separate NEGOTIATE with the same server
+/// and port, offering the same dialects; nothing is authenticated.
+#[rustler::nif(schedule = "DirtyIo")]
+fn server_info<'a>(env: Env<'a>, conn: ResourceArc<Conn>) -> NifResult<Term<'a>> {
+    let config = client_config(&conn.opts).connection;
+    let port = config.port.unwrap_or(445);
+    let neg = negotiate_host(&share_server(&conn.share), port, PROBE_TIMEOUT)
+        .map_err(|e| conn.op_error("negotiate_failed", &e))?;
+
+    let caps = neg.capabilities;
+    let out = ServerInfo {
+        guid: neg.server_guid.to_string(),
+        dialect: format!("{:?}", neg.dialect_revision),
+        dfs: caps.dfs(),
+        leasing: caps.leasing(),
+        large_mtu: caps.large_mtu(),
+        multichannel: caps.multi_channel(),
+        persistent_handles: caps.persistent_handles(),
+        directory_leasing: caps.directory_leasing(),
+        // 3.1.1 announces encryption through its negotiate context instead
+        encryption: caps.encryption() || neg.get_ctx_encrypt_cipher().is_some(),
+        max_read_size: neg.max_read_size,
+        max_write_size: neg.max_write_size,
+        max_transact_size: neg.max_transact_size,
+        max_credits: config.credits_backlog.unwrap_or(DEFAULT_CREDITS),
+    };
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+/// Whether the share refuses writes: `{:ok, true}` once any request on `conn` failed
+/// with STATUS_MEDIA_WRITE_PROTECTED (a read-only share or volume). Otherwise the share
+/// root is opened with MAXIMUM_ALLOWED and the share counts as read-only when the
+/// maximal access the server reports grants neither FILE_ADD_FILE nor
+/// FILE_ADD_SUBDIRECTORY there; nothing is written. A volume that is write-protected
+/// below the share permissions only shows up through the first failed write.
+#[rustler::nif(schedule = "DirtyIo")]
+fn share_readonly<'a>(env: Env<'a>, conn: ResourceArc<Conn>) -> NifResult<Term<'a>> {
+    if conn.write_protected.load(Ordering::Acquire) {
+        return Ok((atoms::ok(), true).encode(env));
+    }
+
+    let access = FileAccessMask::from_bytes(ACCESS_MAXIMUM_ALLOWED.to_le_bytes());
+    let mut client = conn.lock_client()?;
+    let res = open_share_root(&mut client, &conn.share, access)
+        .map_err(|e| conn.create_error("smb_open_failed", &e))?;
+    drop(client);
+
+    let granted = granted_access_of(&res, ACCESS_MAXIMUM_ALLOWED);
+    let readonly = granted & (ACCESS_ADD_FILE | ACCESS_ADD_SUBDIRECTORY) == 0;
+    Ok((atoms::ok(), readonly).encode(env))
+}
+
+/// The full UNC the operations build for `path_in_share`, without touching the server:
+/// `{:ok, "\\\\host\\share\\dir\\name"}`. Separators and surrounding slashes are
+/// handled as by every other NIF; a `..` segment is refused (`bad_segment: '..'`, as
+/// `mkdir_p` does) since the server rejects it anyway.
+#[rustler::nif]
+fn resolve<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    if path_in_share.split(['\\', '/']).any(|seg| seg == "..") {
+        return Err(rustler::Error::Term(Box::new("bad_segment: '..'")));
+    }
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+    Ok((atoms::ok(), unc.to_string()).encode(env))
+}
+
+/// NEGOTIATE only, no credentials: `{:ok, info}` with the dialect the server picks
+/// from the ones we offer, its capability flags and whether it requires signing.
+/// Whether encryption is required is only decided at session setup, so
+/// `encryption_required` is nil. A host that accepts the TCP connect but doesn't
+/// answer with a valid NEGOTIATE within a few seconds gives `{:error, :not_smb}`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn probe<'a>(env: Env<'a>, host: String, port: u16) -> NifResult<Term<'a>> {
+    let addrs: Vec<SocketAddr> = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| connect_error("connect_error", &e))?
+        .collect();
+
+    // reachability first, so a closed or filtered port isn't reported as :not_smb
+    let mut last_err = std::io::Error::new(std::io::ErrorKind::NotFound, "no address");
+    let mut reachable = None;
+    for addr in &addrs {
+        match std::net::TcpStream::connect_timeout(addr, PROBE_TIMEOUT) {
+            Ok(_) => {
+                reachable = Some(addr);
+                break;
+            }
+            Err(e) => last_err = e,
+        }
+    }
+    let Some(addr) = reachable else {
+        return Err(connect_error("connect_error", &last_err));
+    };
+
+    let neg = negotiate_raw(addr, PROBE_TIMEOUT)
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::not_smb())))?;
+
+    let caps = neg.capabilities;
+    let out = ProbeInfo {
+        guid: neg.server_guid.to_string(),
+        dialect: format!("{:?}", neg.dialect_revision),
+        signing_required: neg.security_mode.signing_required(),
+        // 3.1.1 announces encryption through its negotiate context instead
+        encryption: caps.encryption() || neg.get_ctx_encrypt_cipher().is_some(),
+        encryption_required: None,
+        dfs: caps.dfs(),
+        leasing: caps.leasing(),
+        large_mtu: caps.large_mtu(),
+        multichannel: caps.multi_channel(),
+        persistent_handles: caps.persistent_handles(),
+        directory_leasing: caps.directory_leasing(),
+        max_read_size: neg.max_read_size,
+        max_write_size: neg.max_write_size,
+        max_transact_size: neg.max_transact_size,
+    };
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+/// Idle auto-logoff state: `:connected`, `:logged_off` (next op reconnects) or
+/// `:disabled` when the Conn has no `idle_timeout_ms`.
+#[rustler::nif]
+fn idle_state<'a>(env: Env<'a>, conn: ResourceArc<Conn>) -> NifResult<Term<'a>> {
+    let state = match &conn.idle {
+        None => atoms::disabled(),
+        Some(idle) if idle.logged_off.load(Ordering::Acquire) => atoms::logged_off(),
+        Some(_) => atoms::connected(),
+    };
+    Ok((atoms::ok(), state).encode(env))
+}
+
+/// Closes every read handle cached by this Conn (`handle_cache` option).
+#[rustler::nif(schedule = "DirtyIo")]
+fn clear_handle_cache<'a>(env: Env<'a>, conn: ResourceArc<Conn>) -> NifResult<Term<'a>> {
+    conn.clear_handles();
+    Ok(atoms::ok().encode(env))
+}
+
+/// Cumulative transfer counters of this Conn: `bytes_read`, `bytes_written`,
+/// `ops_read`, `ops_written` and `wait_us` (time spent in those transfers).
+#[rustler::nif]
+fn io_stats<'a>(env: Env<'a>, conn: ResourceArc<Conn>) -> NifResult<Term<'a>> {
+    Ok((atoms::ok(), conn.io.snapshot()).encode(env))
+}
+
+#[rustler::nif]
+fn reset_io_stats<'a>(env: Env<'a>, conn: ResourceArc<Conn>) -> NifResult<Term<'a>> {
+    conn.io.reset();
+    Ok(atoms::ok().encode(env))
+}
+
+/// NTSTATUS of the most recent failed operation on this Conn as an integer, or
+/// `nil` when the last operation succeeded (or failed without an NTSTATUS).
+#[rustler::nif]
+fn last_status<'a>(env: Env<'a>, conn: ResourceArc<Conn>) -> NifResult<Term<'a>> {
+    let status = match conn.last_status.load(Ordering::Acquire) {
+        0 => None,
+        s => Some(s),
+    };
+    Ok((atoms::ok(), status).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn read_file<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    read_whole_file(env, &conn, &path_in_share, None, 0)
+}
+
+/// `read_file` opening with extra create options (raw bits, as for `open`), e.g. the
+/// read-ahead hints FILE_SEQUENTIAL_ONLY (0x4) or FILE_RANDOM_ACCESS (0x800). A cached
+/// handle is reused whatever it was opened with.
+#[rustler::nif(schedule = "DirtyIo")]
+fn read_file_with_options<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    options_bits: u32,
+) -> NifResult<Term<'a>> {
+    if options_bits & (OPTIONS_RESERVED_BITS | OPT_DIRECTORY_FILE) != 0 {
+        return Err(rustler::Error::Term(Box::new("bad_options")));
+    }
+    read_whole_file(env, &conn, &path_in_share, None, options_bits)
+}
+
+/// Like `read_file`, but refuses files larger than `max_bytes` with
+/// `{:error, {:too_large, size}}`. The size comes from a metadata query on the open
+/// handle, before any data is read.
+#[rustler::nif(schedule = "DirtyIo")]
+fn read_file_limited<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    max_bytes: u64,
+) -> NifResult<Term<'a>> {
+    read_whole_file(env, &conn, &path_in_share, Some(max_bytes), 0)
+}
+
+/// `read_file` plus what the content cost: `{:ok, bin, info}` with `info` =
+/// `%{size, allocation_size, compressed_size}`. `compressed_size` is the file's
+/// on-disk compressed size when the server answers FileCompressionInformation. SMB2
+/// compression isn't negotiated by this client, so the data arrives uncompressed and
+/// the transfer is `size` bytes.
+#[rustler::nif(schedule = "DirtyIo")]
+fn read_file_info<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let (buf, stdi, compressed_size) = read_whole(&conn, &path_in_share, None, 0, |file| {
+        let ci: FileCompressionInformation = file.query_info().ok()?;
+        Some(ci.compressed_file_size)
+    })?;
+    let info = ReadInfo {
+        size: stdi.end_of_file,
+        allocation_size: stdi.allocation_size,
+        compressed_size,
+    };
+
+    let mut obin = OwnedBinary::new(buf.len())
+        .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
+    obin.as_mut_slice().copy_from_slice(&buf);
+
+    Ok((atoms::ok(), obin.release(env), info).encode(env))
+}
+
+fn read_whole_file<'a>(
+    env: Env<'a>,
+    conn: &Conn,
+    path_in_share: &str,
+    max_bytes: Option<u64>,
+    options_bits: u32,
+) -> NifResult<Term<'a>> {
+    let (buf, _, ()) = read_whole(conn, path_in_share, max_bytes, options_bits, |_| ())?;
+
+    let mut obin = OwnedBinary::new(buf.len())
+        .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
+    obin.as_mut_slice().copy_from_slice(&buf);
+    let bin_term = obin.release(env);
+
+    Ok((atoms::ok(), bin_term).encode(env))
+}
+
+// Whole content of a file, with its standard info and whatever `extra` learns from the
+// open handle before it goes back to the handle cache.
+fn read_whole<T>(
+    conn: &Conn,
+    path_in_share: &str,
+    max_bytes: Option<u64>,
+    options_bits: u32,
+    extra: impl FnOnce(&SmbFile) -> T,
+) -> NifResult<(Vec<u8>, FileStandardInformation, T)> {
+    let rel = path_in_share.trim_start_matches(['\\', '/']);
+    let base = conn.share.to_string();
+    let full = if rel.is_empty() { base } else { format!(r"{}\{}", base.trim_end_matches('\\'), rel) };
+
+    let file_unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    // the Conn's `max_read_bytes` caps every whole-file read, on top of any per-call limit
+    let max_bytes = match (max_bytes, conn.opts.max_read_bytes) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+
+    let started = Instant::now();
+
+    // Size up front: lets us preallocate, and check below that we really got it all.
+    // A cached handle saves the CREATE/CLOSE pair; the size query doubles as its
+    // liveness check (dropped, and a fresh open made, if it fails). smb's File stops
+    // reading at the size it had when opened, so a handle to a file that has changed
+    // size since is dropped too.
+    let mut client = conn.lock_client()?;
+    let cached = conn.take_cached_handle(rel).and_then(|f| {
+        let stdi: FileStandardInformation = f.query_info().ok()?;
+        (f.get_len().ok()? == stdi.end_of_file).then_some((f, stdi))
+    });
+    let (file, stdi) = match cached {
+        Some((f, stdi)) => {
+            drop(client);
+            (f, stdi)
+        }
+        None => {
+            // non-directory open: a directory is refused by the server
+            // (STATUS_FILE_IS_A_DIRECTORY -> :is_a_directory) rather than opened
+            let access = FileAccessMask::new().with_generic_read(true);
+            let mut args = FileCreateArgs::make_open_existing(access);
+            args.options = CreateOptions::default().with_non_directory_file(true);
+            if options_bits != 0 {
+                let bits = u32::from_le_bytes(args.options.into_bytes()) | options_bits;
+                args.options = CreateOptions::from_bytes(bits.to_le_bytes());
+            }
+
+            let resource: Resource = client
+                .create_file(&file_unc, &args)
+                .map_err(|e| conn.open_error("smb_open_failed", &e))?;
+
+            drop(client);
+
+            // servers that ignore FILE_NON_DIRECTORY_FILE still hand out the directory
+            let file: SmbFile = resource
+                .try_into()
+                .map_err(|_| rustler::Error::Term(Box::new(atoms::is_a_directory())))?;
+            let stdi: FileStandardInformation = file
+                .query_info()
+                .map_err(|e| conn.op_error("query_standard_failed", &e))?;
+            (file, stdi)
+        }
+    };
+    check_read_cap(stdi.end_of_file, max_bytes)?;
+    let len: usize = stdi.end_of_file
+        .try_into()
+        .map_err(|_| rustler::Error::Term(Box::new("alloc_failed")))?;
+
+    let mut buf = Vec::new();
+    if conn.channels.is_empty() || len < STRIPE_MIN_BYTES {
+        buf.try_reserve_exact(len)
+            .map_err(|_| rustler::Error::Term(Box::new("alloc_failed")))?;
+        buf.resize(len, 0);
+        // from offset 0 by position: a cached handle's cursor is wherever its last read left it
+        let n = read_up_to_at(&file, &mut buf, 0)
+            .map_err(|e| conn.op_error("smb_read_failed", &e))?;
+        buf.truncate(n);
+    } else {
+        buf.resize(len, 0);
+        striped_read(conn, &file_unc, &file, &mut buf)
+            .map_err(|e| {
+                conn.note_status(&e);
+                rustler::Error::Term(Box::new(e))
+            })?;
+    }
+
+    // Reads the server answers asynchronously (interim STATUS_PENDING) are awaited by the
+    // smb crate until the final response; a premature EOF would show up here as a short read.
+    if buf.len() < len {
+        return Err(rustler::Error::Term(Box::new(format!(
+            "short_read: got {} of {} bytes", buf.len(), len
+        ))));
+    }
+    conn.io.record_read(buf.len(), started);
+    let extra = extra(&file);
+    conn.cache_handle(rel, file);
+
+    Ok((buf, stdi, extra))
+}
+
+/// Sets the per-request timeout of `conn` (0 = none): a request the server doesn't
+/// answer in time fails instead of pinning the dirty scheduler thread. The clients are
+/// reconnected to pick it up, so this waits for any operation in flight; handles
+/// opened before keep the old setting.
+#[rustler::nif(schedule = "DirtyIo")]
+fn set_timeout(conn: ResourceArc<Conn>, ms: u64) -> NifResult<Atom> {
+    // replaced by reconnect below, so a poisoned client needs no health check
+    let mut client = conn.client.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    conn.opts.timeout_ms.store(ms, Ordering::Relaxed);
+    conn.reconnect(&mut client)?;
+    conn.client.clear_poison();
+    if let Some(idle) = &conn.idle {
+        idle.logged_off.store(false, Ordering::Release);
+        idle.touch();
+    }
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn cancel_token() -> ResourceArc<CancelToken> {
+    ResourceArc::new(CancelToken { cancelled: AtomicBool::new(false) })
+}
+
+#[rustler::nif]
+fn cancel(token: ResourceArc<CancelToken>) -> Atom {
+    token.cancelled.store(true, Ordering::Relaxed);
+    atoms::ok()
+}
+
+/// Reads `path_in_share` in 64 KiB chunks and sends `{:line, bin}` to `pid` for every
+/// `\n`-terminated line (without the `\n`; a `\r` before it is left in), then the
+/// unterminated tail if any, then `{:eof}`. Lines are raw bytes — no decoding.
+/// Stops with `{:error, :cancelled}` once `cancel` is set or `pid` is gone; `{:eof}`
+/// is not sent then.
+#[rustler::nif(schedule = "DirtyIo")]
+fn stream_lines<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    pid: LocalPid,
+    cancel: ResourceArc<CancelToken>,
+) -> NifResult<Term<'a>> {
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+    let access = FileAccessMask::new().with_generic_read(true);
+    let args = FileCreateArgs::make_open_existing(access);
+
+    let mut client = conn.lock_client()?;
+    let resource: Resource = client
+        .create_file(&unc, &args)
+        .map_err(|e| conn.open_error("smb_open_failed", &e))?;
+    drop(client);
+
+    let mut file: SmbFile = resource
+        .try_into()
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::not_a_file())))?;
+
+    // OwnedEnv can't send from a (dirty) scheduler thread, so the messages go out from
+    // a sender thread; lines are queued to it and `{:eof}` only after a clean read
+    let gone = AtomicBool::new(false);
+    let read = std::thread::scope(|sc| {
+        let (tx, rx) = mpsc::sync_channel::<Option<OwnedBinary>>(LINE_QUEUE_LEN);
+        let (gone, pid) = (&gone, &pid);
+        sc.spawn(move || {
+            let mut msg_env = OwnedEnv::new();
+            for line in rx {
+                // a failed send means the receiver is dead — treated like a cancel
+                let sent = match line {
+                    Some(bin) => {
+                        msg_env.send_and_clear(pid, move |e| (atoms::line(), bin.release(e)))
+                    }
+                    None => msg_env.send_and_clear(pid, |e| (atoms::eof(),).encode(e)),
+                };
+                if sent.is_err() {
+                    gone.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        });
+
+        let read = read_lines(&conn, &mut file, &cancel, gone, |line| tx.send(Some(line)).is_ok());
+        if read.is_ok() {
+            let _ = tx.send(None);
+        }
+        read
+    });
+
+    read?;
+    if gone.load(Ordering::Relaxed) {
+        return Err(rustler::Error::Term(Box::new(atoms::cancelled())));
+    }
+    Ok(atoms::ok().encode(env))
+}
+
+// stream_lines' read loop: hands every line to `send_line` (false once the sender
+// thread has stopped) until EOF, a cancel or the receiver going away.
+fn read_lines(
+    conn: &Conn,
+    file: &mut SmbFile,
+    cancel: &CancelToken,
+    gone: &AtomicBool,
+    send_line: impl Fn(OwnedBinary) -> bool,
+) -> NifResult<()> {
+    let cancelled = || rustler::Error::Term(Box::new(atoms::cancelled()));
+    let send_line = |bytes: &[u8]| -> NifResult<()> {
+        let mut bin = OwnedBinary::new(bytes.len())
+            .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
+        bin.as_mut_slice().copy_from_slice(bytes);
+        if gone.load(Ordering::Relaxed) || !send_line(bin) {
+            return Err(cancelled());
+        }
+        Ok(())
+    };
+
+    let mut chunk = vec![0u8; LINE_CHUNK_BYTES];
+    let mut pending: Vec<u8> = Vec::new(); // line spanning chunk boundaries
+    loop {
+        if cancel.cancelled.load(Ordering::Relaxed) {
+            return Err(cancelled());
+        }
+        let started = Instant::now();
+        let n = file
+            .read(&mut chunk)
+            .map_err(|e| conn.op_error("smb_read_failed", &e))?;
+        if n == 0 {
+            break;
+        }
+        conn.io.record_read(n, started);
+
+        let mut rest = &chunk[..n];
+        while let Some(i) = rest.iter().position(|&b| b == b'\n') {
+            if pending.is_empty() {
+                send_line(&rest[..i])?;
+            } else {
+                pending.extend_from_slice(&rest[..i]);
+                send_line(&pending)?;
+                pending.clear();
+            }
+            rest = &rest[i + 1..];
+        }
+        pending.extend_from_slice(rest);
+    }
+
+    if !pending.is_empty() {
+        send_line(&pending)?;
+    }
+    Ok(())
+}
+
+// Directories waiting to be listed by walk_parallel's workers. `pending` counts the
+// queued ones plus those being listed; the walk is over when it drops to 0. `seen`
+// holds the file ids of every directory queued so far.
+struct WalkQueue {
+    dirs: VecDeque<String>,
+    pending: usize,
+    seen: HashSet<u64>,
+    stop: bool, // receiver gone
+}
+
+// Sends `msg` to `pid`; Err when the receiver is gone.
+fn send_to<T: Encoder>(msg_env: &mut OwnedEnv, pid: &LocalPid, msg: T) -> Result<(), ()> {
+    msg_env.send_and_clear(pid, |e| msg.encode(e)).map_err(|_| ())
+}
+
+// Lists `rel` (on `own` if given, else on the Conn's client), sends an `{:entry, ..}`
+// per child (`{:offline, path}` for offline files with `skip_offline`) and returns the
+// subdirectories to walk next, with their file ids. Reparse points are reported but not
+// descended into, so junctions can't loop the walk. A directory that can't be listed
+// (fully) is reported as `{:walk_error, rel, reason}`. Err when `pid` is gone.
+fn walk_one(
+    conn: &Conn,
+    own: Option<&Mutex<Client>>,
+    rel: &str,
+    skip_offline: bool,
+    pid: &LocalPid,
+    msg_env: &mut OwnedEnv,
+) -> Result<Vec<(String, u64)>, ()> {
+    let opened = unc_in_share(&conn.share, rel).and_then(|unc| {
+        let args = FileCreateArgs::make_open_existing(list_dir_access());
+        let res = match own {
+            Some(client) => conn.lock_recovering(client)?.create_file(&unc, &args),
+            None => conn.lock_client()?.create_file(&unc, &args),
+        };
+        Ok(res)
+    });
+    let mut skip_dir = |reason: String| -> Result<Vec<(String, u64)>, ()> {
+        send_to(msg_env, pid, (atoms::walk_error(), rel, reason))?;
+        Ok(Vec::new())
+    };
+    let dir: Directory = match opened {
+        Ok(Ok(res)) => match res.try_into() {
+            Ok(dir) => dir,
+            Err(_) => return skip_dir("not_a_directory".to_string()),
+        },
+        Ok(Err(e)) => return skip_dir(format!("smb_open_failed: {e}")),
+        Err(_) => return skip_dir("bad_path".to_string()),
+    };
+    let iter = match dir.query_directory::<FileIdFullDirectoryInformation>("*") {
+        Ok(iter) => iter,
+        Err(e) => return skip_dir(format!("query_failed: {e}")),
+    };
+
+    let mut subdirs = Vec::new();
+    for item in iter {
+        match item {
+            Ok(info) => {
+                let Some(name) = wide_name(&info.file_name) else { continue };
+                if name == "." || name == ".." {
+                    continue;
+                }
+                let path = if rel.is_empty() { name } else { format!("{rel}/{name}") };
+                let attrs = &info.file_attributes;
+                if skip_offline && attrs.offline() && !attrs.directory() {
+                    send_to(msg_env, pid, (atoms::offline(), path.as_str()))?;
+                    continue;
+                }
+                let kind = if attrs.directory() { atoms::directory() } else { atoms::file() };
+                send_to(msg_env, pid, (atoms::entry(), path.as_str(), kind))?;
+                if attrs.directory() && !attrs.reparse_point() {
+                    subdirs.push((path, info.file_id));
+                }
+            }
+            Err(e) => match ntstatus_from_err_display(&e) {
+                Some(STATUS_NO_MORE_FILES) => break,
+                Some(_) => {
+                    send_to(msg_env, pid, (atoms::walk_error(), rel, format!("query_failed: {e}")))?;
+                    break;
+                }
+                None => continue,
+            },
+        }
+    }
+    Ok(subdirs)
+}
+
+fn walk_worker(
+    conn: &Conn,
+    own: Option<&Mutex<Client>>,
+    skip_offline: bool,
+    queue: &Mutex<WalkQueue>,
+    wake: &Condvar,
+    pid: &LocalPid,
+) {
+    let mut msg_env = OwnedEnv::new();
+    loop {
+        let rel = {
+            let mut q = queue.lock().unwrap_or_else(|p| p.into_inner());
+            loop {
+                if q.stop {
+                    return;
+                }
+                if let Some(rel) = q.dirs.pop_front() {
+                    break rel;
+                }
+                if q.pending == 0 {
+                    return;
+                }
+                q = wake.wait(q).unwrap_or_else(|p| p.into_inner());
+            }
+        };
+
+        let found = walk_one(conn, own, &rel, skip_offline, pid, &mut msg_env);
+
+        let mut q = queue.lock().unwrap_or_else(|p| p.into_inner());
+        let mut cycles = Vec::new();
+        match found {
+            Ok(subdirs) => {
+                // a directory already queued under another path (a bind mount or
+                // server-side loop) is reported instead of being walked again; file
+                // systems without stable ids report 0 and are not checked
+                for (path, id) in subdirs {
+                    if id != 0 && !q.seen.insert(id) {
+                        cycles.push(path);
+                        continue;
+                    }
+                    q.pending += 1;
+                    q.dirs.push_back(path);
+                }
+            }
+            Err(()) => q.stop = true,
+        }
+        q.pending -= 1;
+        wake.notify_all();
+        drop(q);
+
+        for path in cycles {
+            if send_to(&mut msg_env, pid, (atoms::cycle_detected(), path.as_str())).is_err() {
+                queue.lock().unwrap_or_else(|p| p.into_inner()).stop = true;
+                wake.notify_all();
+                return;
+            }
+        }
+    }
+}
+
+/// Walks the tree under `root` with `workers` sessions listing directories concurrently
+/// (the Conn's own plus `workers - 1` opened for the walk), sending `pid` one
+/// `{:entry, path, kind}` per file or directory below `root` — each exactly once, in
+/// no particular order — and `{:walk_error, dir, reason}` for directories that could
+/// not be listed. Returns `:ok` once every directory has been listed, or
+/// `{:error, :cancelled}` if `pid` exits first. With `skip_offline`, files with
+/// FILE_ATTRIBUTE_OFFLINE come as `{:offline, path}` instead of `{:entry, ..}`, so a
+/// consumer doesn't open (and recall) them by accident. A directory whose file id was
+/// already walked is sent as `{:cycle_detected, path}` and not descended into.
+#[rustler::nif(schedule = "DirtyIo")]
+fn walk_parallel<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    root: String,
+    pid: LocalPid,
+    workers: usize,
+    skip_offline: bool,
+) -> NifResult<Term<'a>> {
+    let root = root.trim_matches(['\\', '/']).to_string();
+    let workers = workers.clamp(1, MAX_WALK_WORKERS);
+
+    // one extra session per additional worker, dropped (logged off) after the walk
+    let extra = (1..workers)
+        .map(|_| conn.fresh_client().map(Mutex::new))
+        .collect::<NifResult<Vec<_>>>()?;
+
+    let queue = Mutex::new(WalkQueue {
+        dirs: VecDeque::from([root]),
+        pending: 1,
+        seen: HashSet::new(),
+        stop: false,
+    });
+    let wake = Condvar::new();
+    let (conn_ref, queue_ref, wake_ref, pid_ref) = (&*conn, &queue, &wake, &pid);
+    std::thread::scope(|sc| {
+        sc.spawn(move || {
+            walk_worker(conn_ref, None, skip_offline, queue_ref, wake_ref, pid_ref)
+        });
+        for own in &extra {
+            sc.spawn(move || {
+                walk_worker(conn_ref, Some(own), skip_offline, queue_ref, wake_ref, pid_ref)
+            });
+        }
+    });
+
+    if queue.lock().map(|q| q.stop).unwrap_or(true) {
+        return Err(rustler::Error::Term(Box::new(atoms::cancelled())));
+    }
+    Ok(atoms::ok().encode(env))
+}
+
+/// Reads several whole files under one client lock:
+/// `{:ok, [{path, {:ok, bin} | {:error, reason}}]}` in input order. A failing file
+/// only fails its own entry.
+#[rustler::nif(schedule = "DirtyIo")]
+fn read_files<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    paths: Vec<String>,
+) -> NifResult<Term<'a>> {
+    let started = Instant::now();
+    let access = FileAccessMask::new().with_generic_read(true);
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::default().with_non_directory_file(true);
+
+    let mut client = conn.lock_client()?;
+    let mut total = 0usize;
+    let mut out: Vec<(String, Term<'a>)> = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let read = unc_in_share(&conn.share, &path).and_then(|unc| {
+            let res = client
+                .create_file(&unc, &args)
+                .map_err(|e| conn.open_error("smb_open_failed", &e))?;
+            let mut file: SmbFile = res
+                .try_into()
+                .map_err(|_| rustler::Error::Term(Box::new(atoms::is_a_directory())))?;
+            let mut buf = Vec::new();
+            let Some(cap) = conn.opts.max_read_bytes else {
+                file.read_to_end(&mut buf)
+                    .map_err(|e| conn.op_error("smb_read_failed", &e))?;
+                return Ok(buf);
+            };
+            // one byte past the cap is enough to know the file goes on past it
+            (&mut file).take(cap.saturating_add(1)).read_to_end(&mut buf)
+                .map_err(|e| conn.op_error("smb_read_failed", &e))?;
+            if buf.len() as u64 > cap {
+                let stdi: Option<FileStandardInformation> = file.query_info().ok();
+                let size = stdi.map_or(buf.len() as u64, |s| s.end_of_file);
+                return Err(rustler::Error::Term(Box::new((atoms::too_large(), size))));
+            }
+            Ok(buf)
+        });
+
+        let result = match read {
+            Ok(buf) => {
+                total += buf.len();
+                let mut obin = OwnedBinary::new(buf.len())
+                    .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
+                obin.as_mut_slice().copy_from_slice(&buf);
+                (atoms::ok(), obin.release(env)).encode(env)
+            }
+            Err(rustler::Error::Term(reason)) => (atoms::error(), reason.encode(env)).encode(env),
+            Err(_) => (atoms::error(), "bad_path").encode(env),
+        };
+        out.push((path, result));
+    }
+
+    drop(client);
+    conn.io.record_read(total, started);
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+// Applies one FileBasicInformation per path under a single client lock:
+// `{:ok, [{path, :ok | {:error, reason}}]}` in input order. Zero fields are left
+// unchanged by the server.
+fn set_basic_each<'a>(
+    env: Env<'a>,
+    conn: &Conn,
+    items: Vec<(String, FileBasicInformation)>,
+) -> NifResult<Term<'a>> {
+    let access = FileAccessMask::new().with_file_write_attributes(true);
+    let args = FileCreateArgs::make_open_existing(access);
+
+    let mut client = conn.lock_client()?;
+    let mut out: Vec<(String, Term<'a>)> = Vec::with_capacity(items.len());
+
+    for (path, info) in items {
+        let set = unc_in_share(&conn.share, &path).and_then(|unc| {
+            let res = client
+                .create_file(&unc, &args)
+                .map_err(|e| conn.create_error("smb_open_failed", &e))?;
+            let set_err = |e: smb::Error| conn.op_error("set_basic_failed", &e);
+            match res {
+                Resource::File(file) => file.set_file_info(info).map_err(set_err),
+                Resource::Directory(dir) => dir.set_file_info(info).map_err(set_err),
+                _ => Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+            }
+        });
+
+        let result = match set {
+            Ok(()) => atoms::ok().encode(env),
+            Err(rustler::Error::Term(reason)) => (atoms::error(), reason.encode(env)).encode(env),
+            Err(_) => (atoms::error(), "bad_path").encode(env),
+        };
+        out.push((path, result));
+    }
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+/// Sets creation/access/write times of many paths in one go (one lock, one
+/// CREATE + SET_INFO per path): `[{path, mtime_ns, atime_ns, btime_ns}]` in unix
+/// nanoseconds, `nil` leaving that time as is and `:no_change` freezing it.
+/// `{:ok, [{path, :ok | {:error, reason}}]}` — a failing path doesn't stop the rest.
+#[rustler::nif(schedule = "DirtyIo")]
+fn set_times_many<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    items: Vec<(String, UnixNanos, UnixNanos, UnixNanos)>,
+) -> NifResult<Term<'a>> {
+    let items = items
+        .into_iter()
+        .map(|(path, mtime, atime, btime)| {
+            let info = FileBasicInformation {
+                creation_time: unix_nanos_to_filetime(btime).into(),
+                last_access_time: unix_nanos_to_filetime(atime).into(),
+                last_write_time: unix_nanos_to_filetime(mtime).into(),
+                change_time: 0u64.into(),
+                file_attributes: FileAttributes::new(),
+            };
+            (path, info)
+        })
+        .collect();
+    set_basic_each(env, &conn, items)
+}
+
+/// Sets FILE_ATTRIBUTE_* bits of many paths in one go: `[{path, attributes}]`, results
+/// as for `set_times_many`. 0 means "unchanged" on the wire; pass
+/// FILE_ATTRIBUTE_NORMAL (0x80) to clear every attribute of a file.
+#[rustler::nif(schedule = "DirtyIo")]
+fn set_attributes_many<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    items: Vec<(String, u32)>,
+) -> NifResult<Term<'a>> {
+    let items = items
+        .into_iter()
+        .map(|(path, attrs)| {
+            let info = FileBasicInformation {
+                creation_time: 0u64.into(),
+                last_access_time: 0u64.into(),
+                last_write_time: 0u64.into(),
+                change_time: 0u64.into(),
+                file_attributes: FileAttributes::from_bytes(attrs.to_le_bytes()),
+            };
+            (path, info)
+        })
+        .collect();
+    set_basic_each(env, &conn, items)
+}
+
+// Creates `unc` with `args` under `disposition`, answering the CREATE action too. The
+// smb client builds its Resource from the CREATE response without CreateAction and
+// keeps the CREATE itself crate-private, so the action can't be read off one call:
+// the file is created with FILE_CREATE first and only an existing one falls through
+// to `disposition` (a second CREATE). A collision under `:create_new` is `:exists`.
+fn create_with_action(
+    conn: &Conn,
+    client: &mut Client,
+    unc: &UncPath,
+    mut args: FileCreateArgs,
+    disposition: CreateDisposition,
+) -> NifResult<(Resource, Atom)> {
+    args.disposition = CreateDisposition::Create;
+    match client.create_file(unc, &args) {
+        Ok(res) => Ok((res, atoms::created())),
+        Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_OBJECT_NAME_COLLISION) => {
+            if disposition == CreateDisposition::Create {
+                conn.note_status(&e);
+                return Err(rustler::Error::Term(Box::new(atoms::exists())));
+            }
+            args.disposition = disposition;
+            let res = client
+                .create_file(unc, &args)
+                .map_err(|e| conn.open_error("smb_create_failed", &e))?;
+            let action = match disposition {
+                CreateDisposition::OpenIf => atoms::opened(),
+                CreateDisposition::Superseded => atoms::superseded(),
+                _ => atoms::overwritten(),
+            };
+            Ok((res, action))
+        }
+        Err(e) => Err(conn.open_error("smb_create_failed", &e)),
+    }
+}
+
+// write_file's `disposition` atom -> CREATE disposition
+fn write_disposition(disposition: Atom) -> NifResult<CreateDisposition> {
+    match disposition {
+        d if d == atoms::overwrite() => Ok(CreateDisposition::OverwriteIf),
+        d if d == atoms::create_new() => Ok(CreateDisposition::Create),
+        d if d == atoms::open_if() => Ok(CreateDisposition::OpenIf),
+        d if d == atoms::supersede() => Ok(CreateDisposition::Superseded),
+        _ => Err(rustler::Error::Term(Box::new("bad_disposition"))),
+    }
+}
+
+/// Writes `data` to `path_in_share` from offset 0: `{:ok, action}` with the CREATE
+/// action (`:created`, `:overwritten`, `:superseded` or, for `:open_if` on an existing
+/// file, `:opened`). `disposition` is `:overwrite` (create or truncate), `:create_new`
+/// (`{:error, :exists}` if it exists), `:open_if` (no truncation) or `:supersede`.
+/// With `sync`, an SMB2 FLUSH is issued before returning. `attributes` (FILE_ATTRIBUTE_*
+/// bits, 0 for none) are given to a file this creates or replaces; `bad_attributes` for
+/// bits a file can't be created with.
+#[rustler::nif(schedule = "DirtyIo")]
+fn write_file<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    data: Binary<'a>,
+    sync: bool,
+    disposition: Atom,
+    attributes: u32,
+) -> NifResult<Term<'a>> {
+    let disposition = write_disposition(disposition)?;
+    if attributes & !CREATE_ATTRIBUTES != 0 {
+        return Err(rustler::Error::Term(Box::new("bad_attributes")));
+    }
+    let rel = path_in_share.trim_start_matches(['\\', '/']);
+    let base = conn.share.to_string();
+    let full = if rel.is_empty() { base } else { format!(r"{}\{}", base.trim_end_matches('\\'), rel) };
+
+    let file_unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+
+    let started = Instant::now();
+    conn.forget_handle(rel);
+    let mut client = conn.lock_client()?;
+
+    // create with RW access, disposition as asked; an existing directory is refused by
+    // the server (STATUS_FILE_IS_A_DIRECTORY -> :is_a_directory) instead of opened
+    let opts = CreateOptions::default().with_non_directory_file(true);
+    let attributes = FileAttributes::from_bytes(attributes.to_le_bytes());
+    let mut args = FileCreateArgs::make_overwrite(attributes, opts);
+    args.desired_access = FileAccessMask::new().with_generic_read(true).with_generic_write(true);
+
+    let (resource, action) = create_with_action(&conn, &mut client, &file_unc, args, disposition)?;
+    drop(client);
+
+    let mut file: SmbFile = resource
+        .try_into()
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::is_a_directory())))?;
+
+    if conn.channels.is_empty() || data.len() < STRIPE_MIN_BYTES {
+        file.write_all(data.as_slice())
+            .map_err(|e| conn.write_error("smb_write_failed", &e))?;
+    } else {
+        striped_write(&conn, &file_unc, &file, data.as_slice())
+            .map_err(|e| conn.write_failure(e))?;
+    }
+
+    // SMB2 FLUSH: make the data durable server-side before we report success
+    if sync {
+        file.flush().map_err(|e| conn.write_error("smb_flush_failed", &e))?;
+    }
+
+    conn.io.record_write(data.len(), started);
+    Ok((atoms::ok(), action).encode(env))
+}
+
+/// Copies `path_in_share` into the local file `local_path` (created or truncated) in
+/// 1 MiB reads with up to `max_inflight` of them outstanding, each on its own handle,
+/// so a high-latency link is kept busy instead of waiting out one round-trip per read.
+/// `max_inflight` is clamped to 1..=64. `{:ok, bytes}`; the local file is removed again
+/// if the download fails.
+#[rustler::nif(schedule = "DirtyIo")]
+fn download_file<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    local_path: String,
+    max_inflight: usize,
+) -> NifResult<Term<'a>> {
+    let started = Instant::now();
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+    let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true));
+
+    let mut client = conn.lock_client()?;
+    let inflight = max_inflight.clamp(1, MAX_INFLIGHT);
+
+    let mut handles: Vec<SmbFile> = Vec::with_capacity(inflight);
+    for _ in 0..inflight {
+        let res = client
+            .create_file(&unc, &args)
+            .map_err(|e| conn.open_error("smb_open_failed", &e))?;
+        let file: SmbFile = res
+            .try_into()
+            .map_err(|_| rustler::Error::Term(Box::new(atoms::is_a_directory())))?;
+        handles.push(file);
+    }
+    drop(client);
+
+    let stdi: FileStandardInformation = handles[0]
+        .query_info()
+        .map_err(|e| conn.op_error("query_standard_failed", &e))?;
+    let size = stdi.end_of_file;
+
+    let local = std::fs::File::create(&local_path)
+        .map_err(|e| rustler::Error::Term(Box::new(format!("local_create_failed: {e}"))))?;
+    let local = Mutex::new(local);
+    let (next, failed) = (AtomicU64::new(0), AtomicBool::new(false));
+
+    let result: Result<(), String> = std::thread::scope(|sc| {
+        let workers: Vec<_> = handles
+            .iter()
+            .map(|file| {
+                let (next, failed, local) = (&next, &failed, &local);
+                sc.spawn(move || {
+                    let r = download_chunks(file, size, next, failed, local);
+                    if r.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    r
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|w| w.join().map_err(|_| "download_panicked".to_string())?)
+            .try_fold((), |(), r| r)
+    });
+
+    if let Err(reason) = result {
+        drop(local);
+        let _ = std::fs::remove_file(&local_path);
+        conn.note_status(&reason);
+        return Err(rustler::Error::Term(Box::new(reason)));
+    }
+
+    conn.io.record_read(size as usize, started);
+    Ok((atoms::ok(), size).encode(env))
+}
+
+/// Writes `data` to a hidden temp sibling, flushes it, then renames it over
+/// `path_in_share` (replacing it). Readers see either the old or the new content,
+/// never a partial write. The temp file is removed if any step fails.
+#[rustler::nif(schedule = "DirtyIo")]
+fn write_atomic<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    data: Binary<'a>,
+) -> NifResult<Term<'a>> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    if rel.is_empty() {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+    let tmp_unc = unc_in_share(&conn.share, &temp_sibling(rel))?;
+
+    let started = Instant::now();
+    conn.forget_handle(rel);
+    let mut client = conn.lock_client()?;
+
+    // create-new: never clobber someone else's temp file; DELETE for rename/cleanup
+    let attrs = FileAttributes::default().with_hidden(true);
+    let mut args = FileCreateArgs::make_create_new(attrs, CreateOptions::default());
+    args.desired_access = FileAccessMask::new()
+        .with_generic_read(true)
+        .with_generic_write(true)
+        .with_delete(true);
+
+    let resource: Resource = client
+        .create_file(&tmp_unc, &args)
+        .map_err(|e| conn.open_error("smb_create_failed", &e))?;
+
+    drop(client);
+
+    let mut file: SmbFile = resource
+        .try_into()
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::not_a_file())))?;
+
+    let written = file
+        .write_all(data.as_slice())
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("smb_write_failed: {e}"))
+        .and_then(|_| {
+            let info = FileRenameInformation2 {
+                replace_if_exists: Boolean::from(true),
+                root_directory: 0u64,
+                file_name: rel.replace('/', "\\").as_str().into(),
+            };
+            file.set_file_info(info).map_err(|e| format!("rename_failed: {e}"))
+        });
+
+    if let Err(reason) = written {
+        // best effort: mark the temp file for deletion, it goes away on close
+        let _ = file.set_file_info(FileDispositionInformation {
+            delete_pending: Boolean::from(true),
+        });
+        return Err(conn.write_failure(reason));
+    }
+
+    conn.io.record_write(data.len(), started);
+    Ok(atoms::ok().encode(env))
+}
+
+/// Appends `data` to `path_in_share` (created if missing): `{:ok, :appended}`. When
+/// the file would grow past `max_size`, it is first renamed to `<path>.1` (replacing an
+/// older one) and `data` starts a fresh file: `{:ok, :rotated}`. An empty file is never
+/// rotated, so `data` bigger than `max_size` on its own still gets written.
+#[rustler::nif(schedule = "DirtyIo")]
+fn append_capped<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    data: Binary<'a>,
+    max_size: u64,
+) -> NifResult<Term<'a>> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    if rel.is_empty() {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+    let unc = unc_in_share(&conn.share, rel)?;
+    let rotated_rel = format!("{rel}.1");
+
+    let started = Instant::now();
+    conn.forget_handle(rel);
+    conn.forget_handle(&rotated_rel);
+    // one lock for size check, rename and write: appends through this Conn can't interleave
+    let mut client = conn.lock_client()?;
+
+    let opts = CreateOptions::default().with_non_directory_file(true);
+    let mut args = FileCreateArgs::make_overwrite(FileAttributes::default(), opts);
+    args.disposition = CreateDisposition::OpenIf;
+    args.desired_access = FileAccessMask::new()
+        .with_generic_read(true)
+        .with_generic_write(true)
+        .with_delete(true);
+
+    let mut file: SmbFile = client
+        .create_file(&unc, &args)
+        .map_err(|e| conn.open_error("smb_create_failed", &e))?
+        .try_into()
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::is_a_directory())))?;
+    let stdi: FileStandardInformation = file
+        .query_info()
+        .map_err(|e| conn.op_error("query_standard_failed", &e))?;
+
+    let size = stdi.end_of_file;
+    let rotate = size > 0 && size.saturating_add(data.len() as u64) > max_size;
+    // the end measured through this handle, under the lock; a rotated file starts empty
+    let mut offset = size;
+    if rotate {
+        // renamed through the open handle, so nobody can swap the file in between
+        let info = FileRenameInformation2 {
+            replace_if_exists: Boolean::from(true),
+            root_directory: 0u64,
+            file_name: rotated_rel.replace('/', "\\").as_str().into(),
+        };
+        file.set_file_info(info).map_err(|e| conn.op_error("rename_failed", &e))?;
+        drop(file);
+
+        args.disposition = CreateDisposition::Create;
+        file = client
+            .create_file(&unc, &args)
+            .map_err(|e| conn.create_error("smb_create_failed", &e))?
+            .try_into()
+            .map_err(|_| rustler::Error::Term(Box::new(atoms::is_a_directory())))?;
+        offset = 0;
+    }
+
+    write_all_at(&file, data.as_slice(), offset)
+        .map_err(|e| conn.write_error("smb_write_failed", &e))?;
+    drop(client);
+
+    conn.io.record_write(data.len(), started);
+    let outcome = if rotate { atoms::rotated() } else { atoms::appended() };
+    Ok((atoms::ok(), outcome).encode(env))
+}
+
+/// Creates `path_in_share` only if it does not exist yet (CreateDisposition::Create)
+/// and keeps it open: `{:ok, handle}`, or `{:error, :exists}` if someone else holds it.
+/// With `delete_on_close` the file disappears when the handle is closed, which
+/// makes it a simple cross-node lock file.
+#[rustler::nif(schedule = "DirtyIo")]
+fn create_exclusive<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    delete_on_close: bool,
+) -> NifResult<Term<'a>> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    if rel.is_empty() {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+    let unc = unc_in_share(&conn.share, rel)?;
+
+    let mut client = conn.lock_client()?;
+
+    let opts = CreateOptions::default()
+        .with_non_directory_file(true)
+        .with_delete_on_close(delete_on_close);
+    let access = FileAccessMask::new()
+        .with_generic_read(true)
+        .with_generic_write(true)
+        .with_delete(delete_on_close);
+    let (access_bits, options_bits) =
+        (u32::from_le_bytes(access.into_bytes()), u32::from_le_bytes(opts.into_bytes()));
+    let mut args = FileCreateArgs::make_create_new(FileAttributes::default(), opts);
+    args.desired_access = access;
+
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
+        Err(e) => {
+            return match ntstatus_from_err_display(&e) {
+                Some(STATUS_OBJECT_NAME_COLLISION) => {
+                    Err(rustler::Error::Term(Box::new(atoms::exists())))
+                }
+                _ => Err(conn.create_error("smb_create_failed", &e)),
+            };
+        }
+    };
+
+    drop(client);
+
+    let handle = ResourceArc::new(FileHandle {
+        res: Mutex::new(Some(res)),
+        conn: conn.clone(),
+        path: rel.to_string(),
+        access: access_bits,
+        options: options_bits,
+        file_id: None,
+        pos: AtomicU64::new(0),
+    });
+
+    Ok((atoms::ok(), handle).encode(env))
+}
+
+/// Opens an existing `path_in_share` with a caller-chosen access mask and create options
+/// (raw MS-SMB2 bit values), e.g. `FILE_READ_ATTRIBUTES` alone or `DELETE` without read.
+/// Returns `{:ok, handle, info}` where `info` carries the CREATE response (file id,
+/// create action, end_of_file, attributes, granted access); `bad_access`/`bad_options`
+/// for masks that cannot be valid. With MAXIMUM_ALLOWED in `access_bits` the server
+/// grants whatever the caller is entitled to, and the handle keeps that granted mask.
+#[rustler::nif(schedule = "DirtyIo")]
+fn open<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    access_bits: u32,
+    options_bits: u32,
+) -> NifResult<Term<'a>> {
+    let (handle, info, _kind) = open_handle(&conn, &path_in_share, access_bits, options_bits)?;
+    Ok((atoms::ok(), handle, info).encode(env))
+}
+
+/// Like `open`, but answers what was opened instead of the CREATE response:
+/// `{:ok, handle, :file | :directory | :pipe}`. With neither FILE_DIRECTORY_FILE nor
+/// FILE_NON_DIRECTORY_FILE in `options_bits` one open serves both kinds, so a caller
+/// can decide what to do with the path without opening it a second time.
+#[rustler::nif(schedule = "DirtyIo")]
+fn open_resource<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    access_bits: u32,
+    options_bits: u32,
+) -> NifResult<Term<'a>> {
+    let (handle, _info, kind) = open_handle(&conn, &path_in_share, access_bits, options_bits)?;
+    Ok((atoms::ok(), handle, kind).encode(env))
+}
+
+// open/open_resource: the handle, the server's CREATE response and the kind of
+// resource it refers to.
+fn open_handle(
+    conn: &ResourceArc<Conn>,
+    path_in_share: &str,
+    access_bits: u32,
+    options_bits: u32,
+) -> NifResult<(ResourceArc<FileHandle>, CreateInfo, Atom)> {
+    validate_open_bits(access_bits, options_bits)
+        .map_err(|why| rustler::Error::Term(Box::new(why)))?;
+
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    let unc = unc_in_share(&conn.share, rel)?;
+
+    let mut client = conn.lock_client()?;
 
-    // get client
-    let mut client = conn
-        .client
+    let access = FileAccessMask::from_bytes(access_bits.to_le_bytes());
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::from_bytes(options_bits.to_le_bytes());
+
+    let res = client
+        .create_file(&unc, &args)
+        .map_err(|e| conn.open_error("smb_open_failed", &e))?;
+
+    drop(client);
+
+    let info = create_info_of(&res, access_bits, atoms::opened())
+        .map_err(|e| conn.op_error("smb_query_failed", &e))?;
+    let kind = match &res {
+        Resource::File(_) => atoms::file(),
+        Resource::Directory(_) => atoms::directory(),
+        _ => atoms::pipe(), // named pipe on IPC$ (or a printer)
+    };
+    // keep what was granted rather than MAXIMUM_ALLOWED, so close/1 knows whether the
+    // handle can write and dup_handle/1 asks for the same rights
+    let access_bits = if access_bits & ACCESS_MAXIMUM_ALLOWED != 0 {
+        info.granted_access
+    } else {
+        access_bits
+    };
+    let handle = ResourceArc::new(FileHandle {
+        res: Mutex::new(Some(res)),
+        conn: conn.clone(),
+        path: rel.to_string(),
+        access: access_bits,
+        options: options_bits,
+        file_id: None,
+        pos: AtomicU64::new(0),
+    });
+
+    Ok((handle, info, kind))
+}
+
+// A fresh open of what `handle` refers to, with its access and create options.
+fn open_again(handle: &FileHandle) -> NifResult<Resource> {
+    let conn = &handle.conn;
+    let access = FileAccessMask::from_bytes(handle.access.to_le_bytes());
+    match handle.file_id {
+        Some(id) => open_by_file_id(conn, id, access),
+        None => {
+            // always open-existing: the original may have been created exclusively
+            let mut args = FileCreateArgs::make_open_existing(access);
+            args.options = CreateOptions::from_bytes(handle.options.to_le_bytes());
+            let unc = unc_in_share(&conn.share, &handle.path)?;
+            let mut client = conn.lock_client()?;
+            client
+                .create_file(&unc, &args)
+                .map_err(|e| conn.open_error("smb_open_failed", &e))
+        }
+    }
+}
+
+/// Re-establishes `handle` after its connection was lost or reconnected (idle logoff,
+/// `set_timeout`): the file is opened again on the Conn's current session, with the
+/// same access and options, and a file handle keeps its position, so an interrupted
+/// `handle_write`/`pread` sequence can carry on. This is a new open, not a durable
+/// handle reconnect (the smb client doesn't send the durable-handle create contexts):
+/// byte-range locks, delete-on-close and sharing state from the old open are gone,
+/// and another client may have touched the file in between. `{:error, :closed}` if
+/// `handle` was closed; on failure the handle stays closed.
+#[rustler::nif(schedule = "DirtyIo")]
+fn reopen<'a>(env: Env<'a>, handle: ResourceArc<FileHandle>) -> NifResult<Term<'a>> {
+    let mut guard = handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let old = guard
+        .take()
+        .ok_or_else(|| rustler::Error::Term(Box::new(atoms::closed())))?;
+    // the CLOSE for the old open fails on a dead transport; nothing to keep from it (the
+    // position lives in the handle resource and carries over to the new open as it is)
+    drop(old);
+
+    *guard = Some(open_again(&handle)?);
+
+    Ok(atoms::ok().encode(env))
+}
+
+/// Opens a second, independent handle to the file behind `handle`, with the same
+/// access and create options, so two processes can stream different ranges at once.
+/// `{:error, :closed}` if `handle` was already closed.
+#[rustler::nif(schedule = "DirtyIo")]
+fn dup_handle<'a>(env: Env<'a>, handle: ResourceArc<FileHandle>) -> NifResult<Term<'a>> {
+    if handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?
+        .is_none()
+    {
+        return Err(rustler::Error::Term(Box::new(atoms::closed())));
+    }
+
+    let conn = &handle.conn;
+    let res = open_again(&handle)?;
+
+    let dup = ResourceArc::new(FileHandle {
+        res: Mutex::new(Some(res)),
+        conn: conn.clone(),
+        path: handle.path.clone(),
+        access: handle.access,
+        options: handle.options,
+        file_id: handle.file_id,
+        pos: AtomicU64::new(0),
+    });
+
+    Ok((atoms::ok(), dup).encode(env))
+}
+
+/// Opens the existing file `name` inside the directory behind `dir_handle`, on the same
+/// connection: `{:ok, handle, info}` as for `open`. `mode` is `:read`, `:write` or
+/// `:read_write`. A path join, not a handle-relative open: SMB2 CREATE has no
+/// handle-relative form (names always start at the share root), so the directory
+/// handle only supplies the connection and the path it was opened with, and the server
+/// resolves the joined path afresh. `name` must be a single component: a
+/// name with separators, `.` or `..` is `bad_path`; `{:error, :not_a_directory}` if
+/// `dir_handle` is a file, `{:error, :closed}` once it was closed.
+#[rustler::nif(schedule = "DirtyIo")]
+fn open_in_dir<'a>(
+    env: Env<'a>,
+    dir_handle: ResourceArc<FileHandle>,
+    name: String,
+    mode: Atom,
+) -> NifResult<Term<'a>> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['\\', '/']) {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+    let access = match mode {
+        m if m == atoms::read() => FileAccessMask::new().with_generic_read(true),
+        m if m == atoms::write() => FileAccessMask::new().with_generic_write(true),
+        m if m == atoms::read_write() => {
+            FileAccessMask::new().with_generic_read(true).with_generic_write(true)
+        }
+        _ => return Err(rustler::Error::Term(Box::new("bad_mode"))),
+    };
+
+    match dir_handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?
+        .as_ref()
+    {
+        Some(Resource::Directory(_)) => {}
+        Some(_) => return Err(rustler::Error::Term(Box::new(atoms::not_a_directory()))),
+        None => return Err(rustler::Error::Term(Box::new(atoms::closed()))),
+    }
+
+    let conn = &dir_handle.conn;
+    let rel = if dir_handle.path.is_empty() {
+        name
+    } else {
+        format!(r"{}\{}", dir_handle.path.trim_end_matches(['\\', '/']), name)
+    };
+    let unc = unc_in_share(&conn.share, &rel)?;
+
+    let opts = CreateOptions::default().with_non_directory_file(true);
+    let (access_bits, options_bits) =
+        (u32::from_le_bytes(access.into_bytes()), u32::from_le_bytes(opts.into_bytes()));
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = opts;
+
+    let mut client = conn.lock_client()?;
+    let res = client
+        .create_file(&unc, &args)
+        .map_err(|e| conn.open_error("smb_open_failed", &e))?;
+    drop(client);
+    if access_bits & ACCESS_GENERIC_WRITE != 0 {
+        conn.forget_handle(&rel);
+    }
+
+    let info = create_info_of(&res, access_bits, atoms::opened())
+        .map_err(|e| conn.op_error("smb_query_failed", &e))?;
+    let handle = ResourceArc::new(FileHandle {
+        res: Mutex::new(Some(res)),
+        conn: conn.clone(),
+        path: rel,
+        access: access_bits,
+        options: options_bits,
+        file_id: None,
+        pos: AtomicU64::new(0),
+    });
+
+    Ok((atoms::ok(), handle, info).encode(env))
+}
+
+/// Reads up to `len` bytes at `offset` from an open file handle. Returns
+/// `{:ok, bin, :eof}` when the read reached end-of-file — i.e. `offset + byte_size(bin)`
+/// is at or past the file's EndOfFile when read, so a read ending exactly on the last
+/// byte is already `:eof`, as is any read at or beyond EOF (empty `bin`). Otherwise
+/// `{:ok, bin, :more}` and the caller continues at `offset + byte_size(bin)`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn pread<'a>(
+    env: Env<'a>,
+    handle: ResourceArc<FileHandle>,
+    offset: u64,
+    len: usize,
+) -> NifResult<Term<'a>> {
+    let started = Instant::now();
+    let conn = &handle.conn;
+    let guard = handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let file = match guard.as_ref() {
+        Some(Resource::File(file)) => file,
+        Some(_) => return Err(rustler::Error::Term(Box::new(atoms::not_a_file()))),
+        None => return Err(rustler::Error::Term(Box::new(atoms::closed()))),
+    };
+
+    let stdi: FileStandardInformation = file
+        .query_info()
+        .map_err(|e| conn.op_error("query_standard_failed", &e))?;
+    let left = stdi.end_of_file.saturating_sub(offset);
+    let want = len.min(usize::try_from(left).unwrap_or(usize::MAX));
+
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(want)
+        .map_err(|_| rustler::Error::Term(Box::new("alloc_failed")))?;
+    buf.resize(want, 0);
+    // READs at `offset` itself: the handle's cursor is neither used nor moved
+    let mut got = read_up_to_at(file, &mut buf, offset)
+        .map_err(|e| conn.op_error("smb_read_failed", &e))?;
+    if got < want {
+        // smb's File reads no further than the size the file had when it was opened;
+        // what was written past that since (e.g. through this handle) needs a fresh open
+        if let Resource::File(fresh) = open_again(&handle)? {
+            got += read_up_to_at(&fresh, &mut buf[got..], offset + got as u64)
+                .map_err(|e| conn.op_error("smb_read_failed", &e))?;
+        }
+    }
+    buf.truncate(got);
+    drop(guard);
+    conn.io.record_read(buf.len(), started);
+
+    let at_eof = offset.saturating_add(buf.len() as u64) >= stdi.end_of_file;
+    let tail = if at_eof { atoms::eof() } else { atoms::more() };
+
+    let mut obin = OwnedBinary::new(buf.len())
+        .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
+    obin.as_mut_slice().copy_from_slice(&buf);
+
+    Ok((atoms::ok(), obin.release(env), tail).encode(env))
+}
+
+/// Opens `path_in_share` for sequential writing: `{:ok, handle, info}`, to be driven
+/// with `handle_write`/`flush` and finished with `close` (which flushes). `disposition`
+/// is one of write_file's (`:overwrite`, `:create_new`, `:open_if`, `:supersede`) or
+/// `:append`, which opens or creates the file and starts writing at its end.
+#[rustler::nif(schedule = "DirtyIo")]
+fn open_writer<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    disposition: Atom,
+) -> NifResult<Term<'a>> {
+    let append = disposition == atoms::append();
+    let disposition = if append {
+        CreateDisposition::OpenIf
+    } else {
+        write_disposition(disposition)?
+    };
+
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    if rel.is_empty() {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+    let unc = unc_in_share(&conn.share, rel)?;
+    conn.forget_handle(rel);
+
+    let opts = CreateOptions::default().with_non_directory_file(true);
+    let access = FileAccessMask::new().with_generic_read(true).with_generic_write(true);
+    let (access_bits, options_bits) =
+        (u32::from_le_bytes(access.into_bytes()), u32::from_le_bytes(opts.into_bytes()));
+    let mut args = FileCreateArgs::make_overwrite(FileAttributes::default(), opts);
+    args.desired_access = access;
+
+    let mut client = conn.lock_client()?;
+    let (res, action) = create_with_action(&conn, &mut client, &unc, args, disposition)?;
+    drop(client);
+
+    let info = create_info_of(&res, access_bits, action)
+        .map_err(|e| conn.op_error("smb_query_failed", &e))?;
+    // :append starts writing at the end of file as opened
+    let start = if append { info.end_of_file } else { 0 };
+
+    let handle = ResourceArc::new(FileHandle {
+        res: Mutex::new(Some(res)),
+        conn: conn.clone(),
+        path: rel.to_string(),
+        access: access_bits,
+        options: options_bits,
+        file_id: None,
+        pos: AtomicU64::new(start),
+    });
+
+    Ok((atoms::ok(), handle, info).encode(env))
+}
+
+/// Writes all of `data` at the handle's current position (right after the previous
+/// write): `{:ok, bytes_written}`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn handle_write<'a>(
+    env: Env<'a>,
+    handle: ResourceArc<FileHandle>,
+    data: Binary<'a>,
+) -> NifResult<Term<'a>> {
+    let started = Instant::now();
+    let conn = &handle.conn;
+    let guard = handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let file = match guard.as_ref() {
+        Some(Resource::File(file)) => file,
+        Some(_) => return Err(rustler::Error::Term(Box::new(atoms::not_a_file()))),
+        None => return Err(rustler::Error::Term(Box::new(atoms::closed()))),
+    };
+
+    let pos = handle.pos.load(Ordering::Relaxed);
+    write_all_at(file, data.as_slice(), pos)
+        .map_err(|e| conn.write_error("smb_write_failed", &e))?;
+    handle.pos.store(pos + data.len() as u64, Ordering::Relaxed);
+    drop(guard);
+    conn.io.record_write(data.len(), started);
+
+    Ok((atoms::ok(), data.len()).encode(env))
+}
+
+/// Writes all of `data` at `offset` through the open handle: `{:ok, bytes_written}`.
+/// The offset goes into the WRITE requests themselves, so there is no extra round-trip
+/// and no reopen; the handle's position then moves past this write, so a following
+/// `handle_write` carries on after it. Calls from several processes are serialized by
+/// the handle's lock, each writing its whole `data` at its own offset.
+#[rustler::nif(schedule = "DirtyIo")]
+fn write_at_handle<'a>(
+    env: Env<'a>,
+    handle: ResourceArc<FileHandle>,
+    offset: u64,
+    data: Binary<'a>,
+) -> NifResult<Term<'a>> {
+    let started = Instant::now();
+    let conn = &handle.conn;
+    let guard = handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let file = match guard.as_ref() {
+        Some(Resource::File(file)) => file,
+        Some(_) => return Err(rustler::Error::Term(Box::new(atoms::not_a_file()))),
+        None => return Err(rustler::Error::Term(Box::new(atoms::closed()))),
+    };
+
+    write_all_at(file, data.as_slice(), offset)
+        .map_err(|e| conn.write_error("smb_write_failed", &e))?;
+    handle.pos.store(offset + data.len() as u64, Ordering::Relaxed);
+    drop(guard);
+    conn.io.record_write(data.len(), started);
+
+    Ok((atoms::ok(), data.len()).encode(env))
+}
+
+/// Sets the server-side current byte offset of a handle (FilePositionInformation) and
+/// the handle's own position with it, so the next `handle_write` also starts there.
+/// `pread` keeps its explicit offsets.
+#[rustler::nif(schedule = "DirtyIo")]
+fn set_position<'a>(
+    env: Env<'a>,
+    handle: ResourceArc<FileHandle>,
+    offset: u64,
+) -> NifResult<Term<'a>> {
+    let conn = &handle.conn;
+    let guard = handle
+        .res
         .lock()
         .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let file = match guard.as_ref() {
+        Some(Resource::File(file)) => file,
+        Some(_) => return Err(rustler::Error::Term(Box::new(atoms::not_a_file()))),
+        None => return Err(rustler::Error::Term(Box::new(atoms::closed()))),
+    };
+
+    file.set_file_info(FilePositionInformation { current_byte_offset: offset })
+        .map_err(|e| conn.op_error("set_position_failed", &e))?;
+    handle.pos.store(offset, Ordering::Relaxed);
+
+    Ok(atoms::ok().encode(env))
+}
+
+/// SMB2 FLUSH on an open handle: what was written is durable server-side.
+#[rustler::nif(schedule = "DirtyIo")]
+fn flush<'a>(env: Env<'a>, handle: ResourceArc<FileHandle>) -> NifResult<Term<'a>> {
+    let conn = &handle.conn;
+    let mut guard = handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    match guard.as_mut() {
+        Some(Resource::File(file)) => file
+            .flush()
+            .map_err(|e| conn.write_error("smb_flush_failed", &e))?,
+        Some(_) => return Err(rustler::Error::Term(Box::new(atoms::not_a_file()))),
+        None => return Err(rustler::Error::Term(Box::new(atoms::closed()))),
+    }
+
+    Ok(atoms::ok().encode(env))
+}
+
+/// Closes a handle (idempotent). Delete-on-close handles remove their file here.
+/// Handles opened for writing are flushed first; if that fails the handle is still
+/// closed and the flush error returned.
+#[rustler::nif(schedule = "DirtyIo")]
+fn close<'a>(env: Env<'a>, handle: ResourceArc<FileHandle>) -> NifResult<Term<'a>> {
+    let res = handle
+        .res
+        .lock()
+        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?
+        .take();
+
+    let writable = handle.access & (ACCESS_WRITE_DATA | ACCESS_GENERIC_WRITE) != 0;
+    let flushed = match res {
+        Some(Resource::File(file)) if writable => {
+            let flushed = file.flush().map_err(|e| handle.conn.write_error("smb_flush_failed", &e));
+            drop(file);
+            flushed
+        }
+        other => {
+            drop(other);
+            Ok(())
+        }
+    };
+    // dropping the resource sends SMB2 CLOSE
+    flushed?;
+
+    Ok(atoms::ok().encode(env))
+}
+
+/// `{:ok, [{name, kind}]}`; if the enumeration fails midway, the entries read so far
+/// as `{:partial, entries, reason}` rather than dropping them.
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_dir<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    match collect_dir_entries(&conn, &path_in_share)? {
+        (out, _skipped, None) => Ok((atoms::ok(), out).encode(env)),
+        (out, _skipped, Some(reason)) => Ok((atoms::partial(), out, reason).encode(env)),
+    }
+}
+
+/// Same as `list_dir`, but also reports how many corrupted records were skipped
+/// (`{:partial, entries, skipped, reason}` when enumeration broke off).
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_dir_counted<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    match collect_dir_entries(&conn, &path_in_share)? {
+        (out, skipped, None) => Ok((atoms::ok(), out, skipped).encode(env)),
+        (out, skipped, Some(reason)) => Ok((atoms::partial(), out, skipped, reason).encode(env)),
+    }
+}
+
+/// Number of entries of a directory matching `pattern` (a server-side wildcard, `*`
+/// when empty), `.` and `..` excluded, without building the list:
+/// `{:ok, count, skipped}`, where `skipped` counts the records `list_dir` would skip
+/// too (undecodable names and corrupt records, not in `count`). An enumeration that
+/// broke off gives `{:partial, count, skipped, reason}`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn count_entries<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    pattern: String,
+) -> NifResult<Term<'a>> {
+    let mut count: u64 = 0;
+    // names only: the smallest record the server can send per entry
+    let walked = enumerate_dir(
+        &conn,
+        &path_in_share,
+        &pattern,
+        |info: &FileNamesInformation| &info.file_name,
+        |_, _| count += 1,
+    )?;
+
+    match walked {
+        (skipped, None) => Ok((atoms::ok(), count, skipped).encode(env)),
+        (skipped, Some(reason)) => Ok((atoms::partial(), count, skipped, reason).encode(env)),
+    }
+}
+
+/// Names of the entries of a directory matching `pattern` (`*` when empty), read as
+/// FileNamesInformation — no attributes, so the cheapest listing on the wire and to
+/// build: `{:ok, [name]}`, `.` and `..` excluded, undecodable names skipped. A broken-off
+/// enumeration gives `{:partial, names, reason}`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_names<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    pattern: String,
+) -> NifResult<Term<'a>> {
+    let mut names: Vec<String> = Vec::new();
+    let walked = enumerate_dir(
+        &conn,
+        &path_in_share,
+        &pattern,
+        |info: &FileNamesInformation| &info.file_name,
+        |name, _| names.push(name),
+    )?;
+
+    match walked {
+        (_, None) => Ok((atoms::ok(), names).encode(env)),
+        (_, Some(reason)) => Ok((atoms::partial(), names, reason).encode(env)),
+    }
+}
+
+/// `list_dir` with an explicit directory-information class, to trade entry detail for
+/// enumeration cost: `:id_full` (what `list_dir` uses), `:full` (no file ids), `:both`
+/// (with 8.3 short names, slowest on some servers) or `:names_only` (smallest records,
+/// no attributes, so every kind is `nil`). Same `{:ok, entries}` /
+/// `{:partial, entries, reason}` shape as `list_dir`; `{:error, :bad_class}` otherwise.
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_dir_class<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    class: Atom,
+) -> NifResult<Term<'a>> {
+    let known = [atoms::id_full(), atoms::full(), atoms::both(), atoms::names_only()];
+    if !known.contains(&class) {
+        return Err(rustler::Error::Term(Box::new(atoms::bad_class())));
+    }
+    let mut out: Vec<(String, Option<Atom>)> = Vec::new();
+    let path = path_in_share.as_str();
+    let kind_of = |attrs: &FileAttributes| Some(kind_of_attrs(attrs));
+
+    let (_, broke_off) = if class == atoms::id_full() {
+        enumerate_dir(
+            &conn,
+            path,
+            "*",
+            |i: &FileIdFullDirectoryInformation| &i.file_name,
+            |name, i| out.push((name, kind_of(&i.file_attributes))),
+        )?
+    } else if class == atoms::full() {
+        enumerate_dir(
+            &conn,
+            path,
+            "*",
+            |i: &FileFullDirectoryInformation| &i.file_name,
+            |name, i| out.push((name, kind_of(&i.file_attributes))),
+        )?
+    } else if class == atoms::both() {
+        enumerate_dir(
+            &conn,
+            path,
+            "*",
+            |i: &FileBothDirectoryInformation| &i.file_name,
+            |name, i| out.push((name, kind_of(&i.file_attributes))),
+        )?
+    } else {
+        // no attributes in these records, so no kind either
+        enumerate_dir(
+            &conn,
+            path,
+            "*",
+            |i: &FileNamesInformation| &i.file_name,
+            |name, _| out.push((name, None)),
+        )?
+    };
+
+    match broke_off {
+        None => Ok((atoms::ok(), out).encode(env)),
+        Some(reason) => Ok((atoms::partial(), out, reason).encode(env)),
+    }
+}
+
+/// Like `list_dir`, with each entry's 8.3 short name (FileBothDirectoryInformation):
+/// `[{long_name, short_name | nil, kind}]`; `nil` when the name has no short form
+/// (already 8.3, or short names disabled on the volume). Corrupt records are skipped
+/// and a broken-off enumeration gives `{:partial, entries, reason}`, as for `list_dir`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_dir_both_names<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let mut out: Vec<(String, Option<String>, Atom)> = Vec::new();
+    let walked = enumerate_dir(
+        &conn,
+        &path_in_share,
+        "*",
+        |info: &FileBothDirectoryInformation| &info.file_name,
+        |name, info| {
+            // ShortNameLength is in bytes; the 8.3 name fills at most 12 UTF-16 units
+            let short_len = (usize::from(info.short_name_length) / 2).min(12);
+            let short = String::from_utf16(&info.short_name[..short_len])
+                .ok()
+                .filter(|s| !s.is_empty());
+            out.push((name, short, kind_of_attrs(&info.file_attributes)));
+        },
+    )?;
+
+    match walked {
+        (_, None) => Ok((atoms::ok(), out).encode(env)),
+        (_, Some(reason)) => Ok((atoms::partial(), out, reason).encode(env)),
+    }
+}
+
+/// Like `list_dir`, with each entry's server file id: `[{name, kind, file_id}]`.
+/// The id stays valid across renames and can be passed to the `*_by_id` NIFs.
+/// An enumeration that breaks off gives `{:partial, entries, reason}`, as for `list_dir`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_dir_ids<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let mut out: Vec<(String, Atom, u64)> = Vec::new();
+    let walked = enumerate_dir(
+        &conn,
+        &path_in_share,
+        "*",
+        |info: &FileIdFullDirectoryInformation| &info.file_name,
+        |name, info| out.push((name, kind_of_attrs(&info.file_attributes), info.file_id)),
+    )?;
+
+    match walked {
+        (_, None) => Ok((atoms::ok(), out).encode(env)),
+        (_, Some(reason)) => Ok((atoms::partial(), out, reason).encode(env)),
+    }
+}
+
+/// Opens a file by the id from `list_dir_ids` (even if it was renamed since).
+/// `{:error, :unsupported}` when the server or share doesn't support open-by-id.
+#[rustler::nif(schedule = "DirtyIo")]
+fn open_by_id<'a>(env: Env<'a>, conn: ResourceArc<Conn>, file_id: u64) -> NifResult<Term<'a>> {
+    let access = FileAccessMask::new().with_generic_read(true);
+    let access_bits = u32::from_le_bytes(access.into_bytes());
+    let res = open_by_file_id(&conn, file_id, access)?;
+
+    let handle = ResourceArc::new(FileHandle {
+        res: Mutex::new(Some(res)),
+        conn: conn.clone(),
+        path: format!("#{file_id:016x}"),
+        access: access_bits,
+        options: 0,
+        file_id: Some(file_id),
+        pos: AtomicU64::new(0),
+    });
+
+    Ok((atoms::ok(), handle).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn read_file_by_id<'a>(env: Env<'a>, conn: ResourceArc<Conn>, file_id: u64) -> NifResult<Term<'a>> {
+    let started = Instant::now();
+    let access = FileAccessMask::new().with_generic_read(true);
+    let mut file: SmbFile = open_by_file_id(&conn, file_id, access)?
+        .try_into()
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::not_a_file())))?;
+
+    // capped by the Conn's `max_read_bytes` like every other whole-file read
+    let stdi: FileStandardInformation = file
+        .query_info()
+        .map_err(|e| conn.op_error("query_standard_failed", &e))?;
+    check_read_cap(stdi.end_of_file, conn.opts.max_read_bytes)?;
+    let len: usize = stdi.end_of_file
+        .try_into()
+        .map_err(|_| rustler::Error::Term(Box::new("alloc_failed")))?;
+
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(len)
+        .map_err(|_| rustler::Error::Term(Box::new("alloc_failed")))?;
+    file.read_to_end(&mut buf)
+        .map_err(|e| conn.op_error("smb_read_failed", &e))?;
+    conn.io.record_read(buf.len(), started);
+
+    let mut obin = OwnedBinary::new(buf.len())
+        .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
+    obin.as_mut_slice().copy_from_slice(&buf);
+
+    Ok((atoms::ok(), obin.release(env)).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn file_stats_by_id<'a>(env: Env<'a>, conn: ResourceArc<Conn>, file_id: u64) -> NifResult<Term<'a>> {
+    let access = FileAccessMask::new().with_generic_read(true);
+    let out = rich_stats_of(&conn, open_by_file_id(&conn, file_id, access)?)?;
+    Ok((atoms::ok(), out).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn stat<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    // relative path inside share (without leading \ or /)
+    let rel = path_in_share.trim_start_matches(['\\', '/']);
+
+    // get client
+    let mut client = conn.lock_client()?;
 
     // open resource for reading
-    let args = FileCreateArgs::make_open_existing(
-        FileAccessMask::new().with_generic_read(true),
-    );
+    let access = FileAccessMask::new().with_generic_read(true);
+    if is_share_root(rel) {
+        open_share_root(&mut client, &conn.share, access)
+            .map_err(|e| conn.create_error("smb_open_failed", &e))?;
+        return Ok((atoms::ok(), (0u64, true)).encode(env));
+    }
+
+    // build full UNC: "\\host\share\rel"
+    let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
+    let unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    let args = FileCreateArgs::make_open_existing(access);
 
     let res: Resource = client
         .create_file(&unc, &args)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("smb_open_failed: {e}"))))?;
+        .map_err(|e| conn.create_error("smb_open_failed", &e))?;
 
     // Try to treat as file
     if let Ok(mut file) = <Resource as TryInto<SmbFile>>::try_into(res) {
         // read entirely, size = buffer length
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)
-            .map_err(|e| rustler::Error::Term(Box::new(format!("smb_read_failed: {e}"))))?;
+            .map_err(|e| conn.op_error("smb_read_failed", &e))?;
         let size = buf.len() as u64;
         return Ok((atoms::ok(), (size, false)).encode(env));
     }
 
-    // Otherwise consider it a directory (for share root this is also ok)
+    // Otherwise consider it a directory
     Ok((atoms::ok(), (0u64, true)).encode(env))
 }
 
@@ -328,15 +4267,12 @@ fn mkdir_p<'a>(
         return Ok(atoms::ok().encode(env));
     }
 
-    let mut client = conn
-        .client
-        .lock()
-        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let mut client = conn.lock_client()?;
 
     // Build path by segments: \\host\share\seg1 -> ...\seg1\seg2 -> ...
     let mut acc = conn.share.to_string();
 
-    for seg in rel.split(|c| c == '\\' || c == '/') {
+    for seg in rel.split(['\\', '/']) {
         if seg.is_empty() || seg == "." {
             continue;
         }
@@ -354,249 +4290,887 @@ fn mkdir_p<'a>(
         let attrs = FileAttributes::default().with_directory(true);
         let opts  = CreateOptions::default().with_directory_file(true);
 
-        let mut args = FileCreateArgs::make_create_new(attrs, opts);
-        args.disposition = CreateDisposition::OpenIf;
-        args.desired_access = access;
+        let mut args = FileCreateArgs::make_create_new(attrs, opts);
+        args.disposition = CreateDisposition::OpenIf;
+        args.desired_access = access;
+
+        client
+            .create_file(&unc, &args)
+            .map_err(|e| conn.op_error("mkdir_failed", &e))?;
+    }
+
+    Ok(atoms::ok().encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn mkdir<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    rel_path: String,
+) -> NifResult<Term<'a>> {
+    // Trim leading/trailing slashes
+    let rel = rel_path.trim_matches(['\\', '/']);
+
+    if rel.is_empty() {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+
+    // Full UNC directory
+    let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
+    let unc  = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+
+    // Open client
+    let mut client = conn.lock_client()?;
+
+    // Flags: create ONLY if doesn't exist; without OpenIf
+    let access = FileAccessMask::new()
+    .with_generic_read(true)
+    .with_generic_write(true);
+    let attrs = FileAttributes::default().with_directory(true);
+    let opts  = CreateOptions::default().with_directory_file(true);
+
+    let mut args = FileCreateArgs::make_create_new(attrs, opts);
+    args.desired_access = access; // <- using access, warning will disappear
+
+    match client.create_file(&unc, &args) {
+        Ok(_res) => Ok(atoms::ok().encode(env)),
+        Err(e) => Err(conn.op_error("mkdir_failed", &e)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn exists<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    let mut guard = conn.lock_client()?;
+
+    // the root is asked for like any other path instead of assumed to be there
+    let kind = if is_share_root(rel) {
+        root_kind(&mut guard, &conn.share)
+    } else {
+        let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
+        let unc  = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+        open_for_kind(&mut guard, &unc)
+    };
+
+    let out = match kind {
+        Some(Kind::File)    => atoms::file(),
+        Some(Kind::Dir)     => atoms::directory(),
+        Some(Kind::Symlink) => atoms::symlink(),
+        None => return Err(rustler::Error::Term(Box::new(atoms::not_found()))),
+    };
+    Ok((atoms::ok(), out).encode(env))
+}
+
+/// `exists` for many paths at once: `{:ok, [{path, kind}]}` in input order, `kind` being
+/// `:file`, `:directory`, `:symlink` or `:not_found`. The client lock is taken once for
+/// the whole batch and each path costs one attributes-only open (`open_for_kind`); the
+/// opens still go out one after another, as the client has no pipelined CREATE.
+#[rustler::nif(schedule = "DirtyIo")]
+fn exists_many<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    paths: Vec<String>,
+) -> NifResult<Term<'a>> {
+    let mut client = conn.lock_client()?;
+    let mut out: Vec<(String, Atom)> = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let rel = path.trim_matches(['\\', '/']);
+        let kind = if is_share_root(rel) {
+            root_kind(&mut client, &conn.share)
+        } else {
+            match unc_in_share(&conn.share, rel) {
+                Ok(unc) => open_for_kind(&mut client, &unc),
+                Err(_) => None,
+            }
+        };
+        let kind = match kind {
+            Some(Kind::File)    => atoms::file(),
+            Some(Kind::Dir)     => atoms::directory(),
+            Some(Kind::Symlink) => atoms::symlink(),
+            None => atoms::not_found(),
+        };
+        out.push((path, kind));
+    }
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+/// Deletes a file or empty directory; `:ok` if it is already gone. With `dry_run`
+/// nothing is deleted: `{:ok, [path]}` if it exists, `{:ok, []}` if not.
+///
+/// Deletion is delete-on-close: the server removes the entry once every handle on it
+/// is closed, so a plain `rm` may return while the entry is still visible (delete
+/// pending). With `wait` we re-open until the name is gone, or fail with
+/// `{:error, :delete_pending}` after about a second.
+#[rustler::nif(schedule = "DirtyIo")]
+fn rm<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    dry_run: bool,
+    wait: bool,
+) -> NifResult<Term<'a>> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    if rel.is_empty() {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+
+    // Full UNC
+    let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
+    let unc  = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+
+    // Get client
+    let mut client = conn.lock_client()?;
+
+    // Determine type (file/directory); if already gone — success
+    let kind = match open_for_kind(&mut client, &unc) {
+        Some(_) if dry_run => return Ok((atoms::ok(), vec![rel]).encode(env)),
+        Some(k) => k,
+        None if dry_run => return Ok((atoms::ok(), Vec::<&str>::new()).encode(env)),
+        None    => return Ok(atoms::ok().encode(env)),
+    };
+    conn.forget_handle(rel);
+
+    // Open with DELETE and DELETE_ON_CLOSE
+    let access = FileAccessMask::new()
+        .with_delete(true)
+        .with_generic_read(true)
+        .with_generic_write(true);
+
+    let mut args = FileCreateArgs::make_open_existing(access);
+    let opts = CreateOptions::default().with_delete_on_close(true);
+    args.options = match kind {
+        Kind::Dir => opts.with_directory_file(true),
+        Kind::File => opts.with_non_directory_file(true),
+        // delete the link itself, never its target
+        Kind::Symlink => opts.with_open_reparse_point(true),
+    };
+
+    match smb::client::Client::create_file(&mut client, &unc, &args) {
+        Ok(handle) => {
+            // Handle acquired — object will be deleted on close.
+            drop(handle);
+        }
+        Err(e) => {
+            // Parse NTSTATUS code from error text
+            match ntstatus_from_err_display(&e) {
+                Some(STATUS_OBJECT_NAME_NOT_FOUND) => return Ok(atoms::ok().encode(env)),
+                // Already marked for deletion by someone else
+                Some(STATUS_DELETE_PENDING) => {}
+                Some(STATUS_DIRECTORY_NOT_EMPTY) => {
+                    return Err(rustler::Error::Term(Box::new("dir_not_empty")));
+                }
+                _ => return Err(conn.op_error("rm_failed", &e)),
+            }
+        }
+    }
+
+    if wait {
+        wait_for_delete(&mut client, &unc)?;
+    }
+    Ok(atoms::ok().encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn file_stats<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    file_stats_with(env, &conn, &path_in_share, 0)
+}
+
+/// `file_stats` opening with extra create options (raw bits), e.g. FILE_OPEN_NO_RECALL
+/// (0x0040_0000) so that an offline file on an HSM / tiered share is not recalled just
+/// to read its metadata. Such files show FILE_ATTRIBUTE_OFFLINE (0x1000) in
+/// `attributes`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn file_stats_with_options<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    options_bits: u32,
+) -> NifResult<Term<'a>> {
+    if options_bits & (OPTIONS_RESERVED_BITS | OPT_DIRECTORY_FILE | OPT_NON_DIRECTORY_FILE) != 0 {
+        return Err(rustler::Error::Term(Box::new("bad_options")));
+    }
+    file_stats_with(env, &conn, &path_in_share, options_bits)
+}
+
+fn file_stats_with<'a>(
+    env: Env<'a>,
+    conn: &Conn,
+    path_in_share: &str,
+    options_bits: u32,
+) -> NifResult<Term<'a>> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    let access = FileAccessMask::new().with_generic_read(true);
+
+    // Get client and determine resource type
+    let mut client = conn.lock_client()?;
+
+    // the root is a directory by definition; `options_bits` (recall, read-ahead hints)
+    // don't apply to it
+    if is_share_root(rel) {
+        let res = open_share_root(&mut client, &conn.share, access)
+            .map_err(|e| conn.create_error("smb_open_failed", &e))?;
+        drop(client);
+        return Ok((atoms::ok(), rich_stats_of(conn, res)?).encode(env));
+    }
+
+    // Build full UNC
+    let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
+    let unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+
+    let kind = match open_for_kind(&mut client, &unc) {
+        Some(k) => k,
+        None => {
+            // Object doesn't exist
+            return Err(rustler::Error::Term(Box::new(atoms::not_found())));
+        }
+    };
+
+    // Open handle with READ. For directory set directory_file(true).
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = match kind {
+        Kind::Dir => CreateOptions::default().with_directory_file(true),
+        Kind::File => CreateOptions::default().with_non_directory_file(true),
+        // stats of the link itself
+        Kind::Symlink => CreateOptions::default().with_open_reparse_point(true),
+    };
+    if options_bits != 0 {
+        let bits = u32::from_le_bytes(args.options.into_bytes()) | options_bits;
+        args.options = CreateOptions::from_bytes(bits.to_le_bytes());
+    }
+
+    let res: Resource = client
+        .create_file(&unc, &args)
+        .map_err(|e| conn.create_error("smb_open_failed", &e))?;
+
+    drop(client);
+
+    let mut out = rich_stats_of(conn, res)?;
+    if kind == Kind::Symlink {
+        out.r#type = atoms::symlink();
+    }
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+/// FileBasicInformation (attributes + timestamps) through a FILE_READ_ATTRIBUTES-only
+/// open. Such opens don't conflict with share modes, so this works on files that
+/// another client holds exclusively, where `file_stats` (generic read) would fail.
+#[rustler::nif(schedule = "DirtyIo")]
+fn stat_attrs<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+
+    let access = FileAccessMask::new().with_file_read_attributes(true);
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::default(); // neutral: files and directories alike
+
+    let mut client = conn.lock_client()?;
+    let opened = if is_share_root(&path_in_share) {
+        let access = FileAccessMask::new().with_file_read_attributes(true);
+        open_share_root(&mut client, &conn.share, access)
+    } else {
+        client.create_file(&unc, &args)
+    };
+    let (res, symlink) = match opened {
+        Ok(res) => (res, false),
+        Err(e) => match ntstatus_from_err_display(&e) {
+            Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+                return Err(rustler::Error::Term(Box::new(atoms::not_found())));
+            }
+            Some(STATUS_STOPPED_ON_SYMLINK) => {
+                // attributes of the link itself
+                args.options = CreateOptions::default().with_open_reparse_point(true);
+                let res = client
+                    .create_file(&unc, &args)
+                    .map_err(|e| conn.create_error("smb_open_failed", &e))?;
+                (res, true)
+            }
+            _ => return Err(conn.create_error("smb_open_failed", &e)),
+        },
+    };
+    drop(client);
+
+    let basic_err = |e: smb::Error| conn.op_error("query_basic_failed", &e);
+    let (kind, basic): (Atom, FileBasicInformation) = match res {
+        Resource::File(file) => (atoms::file(), file.query_info().map_err(basic_err)?),
+        Resource::Directory(dir) => (atoms::directory(), dir.query_info().map_err(basic_err)?),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+
+    let out = AttrStats {
+        r#type: if symlink { atoms::symlink() } else { kind },
+        attributes: u32::from_le_bytes(basic.file_attributes.into_bytes()),
+        mtime: filetime_to_unix_seconds(*basic.last_write_time),
+        atime: filetime_to_unix_seconds(*basic.last_access_time),
+        ctime: filetime_to_unix_seconds(*basic.change_time),
+        btime: filetime_to_unix_seconds(*basic.creation_time),
+        mtime_ns: filetime_to_unix_nanos(*basic.last_write_time),
+        atime_ns: filetime_to_unix_nanos(*basic.last_access_time),
+        ctime_ns: filetime_to_unix_nanos(*basic.change_time),
+        btime_ns: filetime_to_unix_nanos(*basic.creation_time),
+    };
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+/// `{:ok, true}` when the last write time of `path_in_share` is after `unix_seconds`,
+/// compared in whole seconds like the `mtime` of `file_stats`/`stat_attrs` (so an mtime
+/// stored from either compares equal, not newer). One FILE_READ_ATTRIBUTES open plus a
+/// FileBasicInformation query; `{:error, :not_found}` when nothing is there.
+#[rustler::nif(schedule = "DirtyIo")]
+fn newer_than<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    unix_seconds: i64,
+) -> NifResult<Term<'a>> {
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+
+    let access = FileAccessMask::new().with_file_read_attributes(true);
+    let mut args = FileCreateArgs::make_open_existing(access);
+    args.options = CreateOptions::default(); // neutral: files and directories alike
+
+    let mut client = conn.lock_client()?;
+    let res = client
+        .create_file(&unc, &args)
+        .map_err(|e| conn.open_error("smb_open_failed", &e))?;
+    drop(client);
+
+    let basic_err = |e: smb::Error| conn.op_error("query_basic_failed", &e);
+    let basic: FileBasicInformation = match res {
+        Resource::File(file) => file.query_info().map_err(basic_err)?,
+        Resource::Directory(dir) => dir.query_info().map_err(basic_err)?,
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+
+    let mtime = filetime_to_unix_seconds(*basic.last_write_time);
+    let newer = i64::try_from(mtime).map_or(true, |m| m > unix_seconds);
+    Ok((atoms::ok(), newer).encode(env))
+}
+
+/// `{:ok, true}` when `path_in_share` is a junction / mount point
+/// (IO_REPARSE_TAG_MOUNT_POINT). Symlinks and other reparse points are `false`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn is_junction<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+
+    // probes the reparse point itself, never what it points to
+    let Some(entry) = probe_entry(&conn, &unc)? else {
+        return Err(rustler::Error::Term(Box::new(atoms::not_found())));
+    };
+    let junction = entry.reparse_tag == Some(IO_REPARSE_TAG_MOUNT_POINT);
+
+    Ok((atoms::ok(), junction).encode(env))
+}
+
+/// `{:ok, ["@GMT-YYYY.MM.DD-HH.MM.SS", ...]}` — the shadow copies (previous versions)
+/// the server has for `path_in_share`, via FSCTL_SRV_ENUMERATE_SNAPSHOTS.
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_snapshots<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+
+    // the IOCTL needs read access on the handle; make_open_existing's options are
+    // neutral, so files and directories open alike
+    let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true));
+
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
+        Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+            conn.note_status(&e);
+            return Err(rustler::Error::Term(Box::new(atoms::not_found())));
+        }
+        Err(e) => return Err(conn.create_error("smb_open_failed", &e)),
+    };
+    drop(client);
+
+    let handle: &ResourceHandle = match &res {
+        Resource::File(file) => file,
+        Resource::Directory(dir) => dir,
+        Resource::Pipe(pipe) => pipe.handle(),
+    };
+    let snaps = handle
+        .fsctl(SrvEnumerateSnapshotsRequest::new(()))
+        .map_err(|e| match ntstatus_from_err_display(&e) {
+            Some(STATUS_NOT_SUPPORTED) | Some(STATUS_INVALID_DEVICE_REQUEST) => {
+                conn.note_status(&e);
+                rustler::Error::Term(Box::new(atoms::unsupported()))
+            }
+            _ => conn.op_error("enumerate_snapshots_failed", &e),
+        })?;
+
+    let labels: Vec<String> = snaps.snap_shots.iter().map(|s| s.to_string()).collect();
+    Ok((atoms::ok(), labels).encode(env))
+}
+
+/// Raw SMB2 SET_INFO escape hatch: opens `path_in_share` and sets file-information
+/// class `info_class` from `buffer`, which must be exactly that class's wire encoding
+/// (little-endian, as in MS-FSCC). Classes the smb crate can encode: 4 (Basic),
+/// 14 (Position), 16 (Mode), 19 (Allocation), 20 (EndOfFile), 39 (ValidDataLength);
+/// others are `{:error, :unsupported}`. A buffer that doesn't parse as the class is
+/// `{:error, "bad_buffer"}`; a server rejection is `{:error, {:ntstatus, code}}`.
+/// Position and Mode only apply to the handle opened here, which is closed again.
+#[rustler::nif(schedule = "DirtyIo")]
+fn set_info<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    info_class: u8,
+    buffer: Binary<'a>,
+) -> NifResult<Term<'a>> {
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+    conn.forget_handle(&path_in_share);
+
+    let access = FileAccessMask::new()
+        .with_generic_read(true)
+        .with_generic_write(true);
+    let args = FileCreateArgs::make_open_existing(access);
+
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
+        Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+            conn.note_status(&e);
+            return Err(rustler::Error::Term(Box::new(atoms::not_found())));
+        }
+        Err(e) => return Err(conn.create_error("smb_open_failed", &e)),
+    };
+    drop(client);
 
-        client
-            .create_file(&unc, &args)
-            .map_err(|e| rustler::Error::Term(Box::new(format!("mkdir_failed: {e}"))))?;
+    // parse `buffer` as `$t` (all of it) and send it on whichever resource we got
+    macro_rules! set_as {
+        ($t:ty) => {{
+            let mut cur = std::io::Cursor::new(buffer.as_slice());
+            let info = <$t>::read_le(&mut cur)
+                .ok()
+                .filter(|_| cur.position() == buffer.len() as u64)
+                .ok_or_else(|| rustler::Error::Term(Box::new("bad_buffer")))?;
+            match &res {
+                Resource::File(file) => file.set_file_info(info),
+                Resource::Directory(dir) => dir.set_file_info(info),
+                _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+            }
+        }};
     }
 
+    let result = match info_class {
+        4 => set_as!(FileBasicInformation),
+        14 => set_as!(FilePositionInformation),
+        16 => set_as!(FileModeInformation),
+        19 => set_as!(FileAllocationInformation),
+        20 => set_as!(FileEndOfFileInformation),
+        39 => set_as!(FileValidDataLengthInformation),
+        _ => return Err(rustler::Error::Term(Box::new(atoms::unsupported()))),
+    };
+
+    result.map_err(|e| match ntstatus_from_err_display(&e) {
+        // EndOfFile / Allocation growing past the free space
+        Some(STATUS_DISK_FULL) => conn.write_error("set_info_failed", &e),
+        Some(code) => {
+            conn.note_status(&e);
+            rustler::Error::Term(Box::new((atoms::ntstatus(), code)))
+        }
+        None => conn.op_error("set_info_failed", &e),
+    })?;
     Ok(atoms::ok().encode(env))
 }
 
+/// Owner of `path_in_share` as a SID string (`"S-1-5-21-..."`), or nil if the security
+/// descriptor has none. Opens with READ_CONTROL only and asks for nothing but
+/// OWNER_SECURITY_INFORMATION, so no DACL is transferred. `{:error, :access_denied}`
+/// without READ_CONTROL on the object.
 #[rustler::nif(schedule = "DirtyIo")]
-fn mkdir<'a>(
+fn get_owner<'a>(
     env: Env<'a>,
     conn: ResourceArc<Conn>,
-    rel_path: String,
+    path_in_share: String,
 ) -> NifResult<Term<'a>> {
-    // Trim leading/trailing slashes
-    let rel = rel_path.trim_matches(['\\', '/']);
-
-    if rel.is_empty() {
-        return Err(rustler::Error::Term(Box::new("bad_path")));
-    }
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+    let denied = |e: &smb::Error| {
+        conn.note_status(e);
+        rustler::Error::Term(Box::new(atoms::access_denied()))
+    };
 
-    // Full UNC directory
-    let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
-    let unc  = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_read_control(true));
 
-    // Open client
-    let mut client = conn
-        .client
-        .lock()
-        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
+        Err(e) => {
+            return Err(match ntstatus_from_err_display(&e) {
+                Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+                    conn.note_status(&e);
+                    rustler::Error::Term(Box::new(atoms::not_found()))
+                }
+                Some(STATUS_ACCESS_DENIED) => denied(&e),
+                _ => conn.create_error("smb_open_failed", &e),
+            })
+        }
+    };
+    drop(client);
 
-    // Flags: create ONLY if doesn't exist; without OpenIf
-    let access = FileAccessMask::new()
-    .with_generic_read(true)
-    .with_generic_write(true);
-    let attrs = FileAttributes::default().with_directory(true);
-    let opts  = CreateOptions::default().with_directory_file(true);
+    let wanted = AdditionalInfo::new().with_owner_security_information(true);
+    let sd: Result<SecurityDescriptor, smb::Error> = match &res {
+        Resource::File(file) => file.query_security_info(wanted),
+        Resource::Directory(dir) => dir.query_security_info(wanted),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+    let sd = sd.map_err(|e| match ntstatus_from_err_display(&e) {
+        Some(STATUS_ACCESS_DENIED) => denied(&e),
+        _ => conn.op_error("query_security_failed", &e),
+    })?;
 
-    let mut args = FileCreateArgs::make_create_new(attrs, opts);
-    args.desired_access = access; // <- using access, warning will disappear
+    Ok((atoms::ok(), sd.owner_sid.map(|sid| sid.to_string())).encode(env))
+}
 
-    match client.create_file(&unc, &args) {
-        Ok(_res) => Ok(atoms::ok().encode(env)),
-        Err(e) => Err(rustler::Error::Term(Box::new(format!("mkdir_failed: {e}")))),
+// "S-1-<authority>-<sub>..." with 1..=15 sub-authorities: authority a decimal or 0x-hex
+// 48-bit value, each sub-authority a decimal u32.
+fn parse_sid(s: &str) -> Option<SID> {
+    let mut parts = s.strip_prefix("S-1-")?.split('-');
+    let authority = parts.next()?;
+    let authority = match authority.strip_prefix("0x").or_else(|| authority.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => authority.parse::<u64>().ok(),
+    };
+    let authority_ok = authority.is_some_and(|a| a < 1 << 48);
+    let subs: Vec<&str> = parts.collect();
+    let subs_ok = (1..=15).contains(&subs.len())
+        && subs.iter().all(|p| !p.starts_with('+') && p.parse::<u32>().is_ok());
+    if !(authority_ok && subs_ok) {
+        return None;
     }
+    SID::from_str(s).ok()
 }
 
+/// Makes `sid` the owner of `path_in_share` (OWNER_SECURITY_INFORMATION only; the DACL
+/// is left alone). Needs WRITE_OWNER on the object, or SeRestorePrivilege to assign an
+/// owner other than the caller. `{:error, :bad_sid}` for a malformed SID string,
+/// `{:error, :access_denied}` when the server refuses.
 #[rustler::nif(schedule = "DirtyIo")]
-fn exists<'a>(
+fn set_owner<'a>(
     env: Env<'a>,
     conn: ResourceArc<Conn>,
     path_in_share: String,
+    sid: String,
 ) -> NifResult<Term<'a>> {
-    let rel = path_in_share.trim_matches(['\\', '/']);
-    if rel.is_empty() {
-        return Ok((atoms::ok(), atoms::directory()).encode(env));
-    }
+    let owner = parse_sid(&sid).ok_or_else(|| rustler::Error::Term(Box::new(atoms::bad_sid())))?;
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+    let denied = |e: &smb::Error| {
+        conn.note_status(e);
+        rustler::Error::Term(Box::new(atoms::access_denied()))
+    };
 
-    let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
-    let unc  = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_write_owner(true));
 
-    let mut guard = conn.client.lock()
-        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
+        Err(e) => {
+            return Err(match ntstatus_from_err_display(&e) {
+                Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+                    conn.note_status(&e);
+                    rustler::Error::Term(Box::new(atoms::not_found()))
+                }
+                Some(STATUS_ACCESS_DENIED) => denied(&e),
+                _ => conn.create_error("smb_open_failed", &e),
+            })
+        }
+    };
+    drop(client);
 
-    let out = match open_for_kind(&mut *guard, &unc) {
-        Some(Kind::File) => atoms::file(),
-        Some(Kind::Dir)  => atoms::directory(),
-        None             => atoms::not_found(),
+    // a self-relative descriptor carrying the owner only
+    let sd = SecurityDescriptor {
+        sbz1: 0,
+        control: SecurityDescriptorControl::new().with_self_relative(true),
+        owner_sid: Some(owner),
+        group_sid: None,
+        sacl: None,
+        dacl: None,
     };
-    Ok((atoms::ok(), out).encode(env))
+    let which = AdditionalInfo::new().with_owner_security_information(true);
+    let set = match &res {
+        Resource::File(file) => file.set_security_info(sd, which),
+        Resource::Directory(dir) => dir.set_security_info(sd, which),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+    set.map_err(|e| match ntstatus_from_err_display(&e) {
+        Some(STATUS_ACCESS_DENIED) | Some(STATUS_INVALID_OWNER) => denied(&e),
+        _ => conn.op_error("set_security_failed", &e),
+    })?;
+
+    Ok(atoms::ok().encode(env))
 }
 
+/// Sets (`protected = true`) or clears the SE_DACL_PROTECTED control bit on the DACL of
+/// `path_in_share`, leaving its ACE list as it is. A protected DACL stops inheriting from
+/// the parent: ACEs already inherited stay in the list until replaced. Needs READ_CONTROL
+/// and WRITE_DAC; `{:error, :access_denied}` without them.
 #[rustler::nif(schedule = "DirtyIo")]
-fn rm<'a>(
+fn set_dacl_protected<'a>(
     env: Env<'a>,
     conn: ResourceArc<Conn>,
     path_in_share: String,
+    protected: bool,
 ) -> NifResult<Term<'a>> {
-    let rel = path_in_share.trim_matches(['\\', '/']);
-    if rel.is_empty() {
-        return Err(rustler::Error::Term(Box::new("bad_path")));
-    }
-
-    // Full UNC
-    let full = format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel);
-    let unc  = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
-
-    // Get client
-    let mut client = conn
-        .client
-        .lock()
-        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
-
-    // Determine type (file/directory); if already gone — success
-    let kind = match open_for_kind(&mut *client, &unc) {
-        Some(k) => k,
-        None    => return Ok(atoms::ok().encode(env)),
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
+    let denied = |e: &smb::Error| {
+        conn.note_status(e);
+        rustler::Error::Term(Box::new(atoms::access_denied()))
     };
 
-    // Open with DELETE and DELETE_ON_CLOSE
-    let access = FileAccessMask::new()
-        .with_delete(true)
-        .with_generic_read(true)
-        .with_generic_write(true);
-
-    let mut args = FileCreateArgs::make_open_existing(access);
-    let mut opts = CreateOptions::default().with_delete_on_close(true);
-    if matches!(kind, Kind::Dir) {
-        opts = opts.with_directory_file(true);
-    } else {
-        opts = opts.with_non_directory_file(true);
-    }
-    args.options = opts;
+    let access = FileAccessMask::new().with_read_control(true).with_write_dacl(true);
+    let args = FileCreateArgs::make_open_existing(access);
 
-    match smb::client::Client::create_file(&mut *client, &unc, &args) {
-        Ok(handle) => {
-            // Handle acquired — object will be deleted on close. Return success without waiting.
-            drop(handle);
-            Ok(atoms::ok().encode(env))
-        }
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
         Err(e) => {
-            // Parse NTSTATUS code from error text
-            match ntstatus_from_err_display(&e) {
-                Some(STATUS_OBJECT_NAME_NOT_FOUND) |
-                Some(STATUS_DELETE_PENDING) => {
-                    // Already deleted or marked for deletion — consider success
-                    Ok(atoms::ok().encode(env))
+            return Err(match ntstatus_from_err_display(&e) {
+                Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+                    conn.note_status(&e);
+                    rustler::Error::Term(Box::new(atoms::not_found()))
                 }
-                Some(STATUS_DIRECTORY_NOT_EMPTY) => {
-                    Err(rustler::Error::Term(Box::new("dir_not_empty")))
-                }
-                _ => Err(rustler::Error::Term(Box::new(format!("rm_failed: {e}")))),
-            }
+                Some(STATUS_ACCESS_DENIED) => denied(&e),
+                _ => conn.create_error("smb_open_failed", &e),
+            })
         }
+    };
+    drop(client);
+
+    let which = AdditionalInfo::new().with_dacl_security_information(true);
+    let current: Result<SecurityDescriptor, smb::Error> = match &res {
+        Resource::File(file) => file.query_security_info(which),
+        Resource::Directory(dir) => dir.query_security_info(which),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+    let current = current.map_err(|e| match ntstatus_from_err_display(&e) {
+        Some(STATUS_ACCESS_DENIED) => denied(&e),
+        _ => conn.op_error("query_security_failed", &e),
+    })?;
+    if current.control.dacl_protected() == protected {
+        return Ok(atoms::ok().encode(env));
     }
+
+    // Send the DACL back unchanged, only the control bit differs
+    let sd = SecurityDescriptor {
+        sbz1: 0,
+        control: current
+            .control
+            .with_self_relative(true)
+            .with_sacl_present(false)
+            .with_dacl_protected(protected),
+        owner_sid: None,
+        group_sid: None,
+        sacl: None,
+        dacl: current.dacl,
+    };
+    let set = match &res {
+        Resource::File(file) => file.set_security_info(sd, which),
+        Resource::Directory(dir) => dir.set_security_info(sd, which),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+    set.map_err(|e| match ntstatus_from_err_display(&e) {
+        Some(STATUS_ACCESS_DENIED) => denied(&e),
+        _ => conn.op_error("set_security_failed", &e),
+    })?;
+
+    Ok(atoms::ok().encode(env))
 }
 
+/// Counterpart of `set_info`: `{:ok, bin}` with file-information class `info_class` of
+/// `path_in_share` in its MS-FSCC wire encoding, for classes without a typed wrapper.
+/// Supported: 4 (Basic), 5 (Standard), 6 (Internal), 7 (Ea), 8 (Access), 14 (Position),
+/// 16 (Mode), 17 (Alignment), 28 (Compression), 34 (NetworkOpen), 35 (AttributeTag),
+/// 48 (NormalizedName), 59 (Id). The buffer is re-encoded from the parsed response,
+/// so it has exactly the class's layout. Other classes, or classes the server
+/// rejects, are `{:error, :unsupported}`.
 #[rustler::nif(schedule = "DirtyIo")]
-fn file_stats<'a>(
+fn query_info_raw<'a>(
     env: Env<'a>,
     conn: ResourceArc<Conn>,
     path_in_share: String,
+    info_class: u8,
 ) -> NifResult<Term<'a>> {
-    // Build full UNC
-    let rel = path_in_share.trim_matches(['\\', '/']);
-    let full = if rel.is_empty() {
-        conn.share.to_string()
-    } else {
-        format!(r"{}\{}", conn.share.to_string().trim_end_matches('\\'), rel)
-    };
-    let unc = UncPath::from_str(&full).map_err(|_| rustler::Error::BadArg)?;
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
 
-    // Get client and determine resource type
-    let mut client = conn
-        .client
-        .lock()
-        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let access = FileAccessMask::new()
+        .with_file_read_attributes(true)
+        .with_file_read_ea(true)
+        .with_read_control(true);
+    let args = FileCreateArgs::make_open_existing(access);
 
-    let kind = match open_for_kind(&mut *client, &unc) {
-        Some(k) => k,
-        None => {
-            // Object doesn't exist
-            return Ok((atoms::ok(), atoms::not_found()).encode(env));
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
+        Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+            conn.note_status(&e);
+            return Err(rustler::Error::Term(Box::new(atoms::not_found())));
         }
+        Err(e) => return Err(conn.create_error("smb_open_failed", &e)),
     };
+    drop(client);
 
-    // Open handle with READ. For directory set directory_file(true).
-    let access = FileAccessMask::new().with_generic_read(true);
-    let mut args = FileCreateArgs::make_open_existing(access);
-    args.options = match kind {
-        Kind::Dir => CreateOptions::default().with_directory_file(true),
-        Kind::File => CreateOptions::default().with_non_directory_file(true),
+    // query as `$t` on whichever resource we got, then encode it back to bytes
+    macro_rules! query_as {
+        ($t:ty) => {{
+            let info: Result<$t, smb::Error> = match &res {
+                Resource::File(file) => file.query_info(),
+                Resource::Directory(dir) => dir.query_info(),
+                _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+            };
+            info.map(|info| {
+                let mut out = std::io::Cursor::new(Vec::new());
+                info.write_le(&mut out).map(|_| out.into_inner())
+            })
+        }};
+    }
+
+    let queried = match info_class {
+        4 => query_as!(FileBasicInformation),
+        5 => query_as!(FileStandardInformation),
+        6 => query_as!(FileInternalInformation),
+        7 => query_as!(FileEaInformation),
+        8 => query_as!(FileAccessInformation),
+        14 => query_as!(FilePositionInformation),
+        16 => query_as!(FileModeInformation),
+        17 => query_as!(FileAlignmentInformation),
+        28 => query_as!(FileCompressionInformation),
+        34 => query_as!(FileNetworkOpenInformation),
+        35 => query_as!(FileAttributeTagInformation),
+        48 => query_as!(FileNormalizedNameInformation),
+        59 => query_as!(FileIdInformation),
+        _ => return Err(rustler::Error::Term(Box::new(atoms::unsupported()))),
     };
 
-    let res: Resource = client
-        .create_file(&unc, &args)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("smb_open_failed: {e}"))))?;
+    let bytes = queried
+        .map_err(|e| match ntstatus_from_err_display(&e) {
+            Some(STATUS_NOT_SUPPORTED)
+            | Some(STATUS_INVALID_PARAMETER)
+            | Some(STATUS_INVALID_INFO_CLASS) => {
+                conn.note_status(&e);
+                rustler::Error::Term(Box::new(atoms::unsupported()))
+            }
+            _ => conn.op_error("query_info_failed", &e),
+        })?
+        .map_err(|e| rustler::Error::Term(Box::new(format!("encode_failed: {e}"))))?;
 
-    drop(client);
+    let mut obin = OwnedBinary::new(bytes.len())
+        .ok_or_else(|| rustler::Error::Term(Box::new("alloc_failed")))?;
+    obin.as_mut_slice().copy_from_slice(&bytes);
+    Ok((atoms::ok(), obin.release(env)).encode(env))
+}
 
-    // Unified get FileBasicInformation + FileStandardInformation
-    // depending on type (both File and Directory support query_info via Deref<ResourceHandle>)
-    let (size, alloc, nlink, attrs_bits, mtime, atime, ctime, btime) = match kind {
-        Kind::File => {
-            let file: SmbFile = res
-                .try_into()
-                .map_err(|_| rustler::Error::Term(Box::new("not_a_file")))?;
-            let basic: FileBasicInformation = file
-                .query_info()
-                .map_err(|e| rustler::Error::Term(Box::new(format!("query_basic_failed: {e}"))))?;
-            let stdi: FileStandardInformation = file
-                .query_info()
-                .map_err(|e| rustler::Error::Term(Box::new(format!("query_standard_failed: {e}"))))?;
+/// `{:ok, path}` — the server's canonical share-relative path for `path_in_share`,
+/// with case as stored and 8.3 short names expanded (`progra~1/x` -> `Program Files/X`).
+/// Uses FileNormalizedNameInformation (SMB 3.1.1); older servers fall back to the
+/// name in FileAllInformation, which may keep short components as given.
+#[rustler::nif(schedule = "DirtyIo")]
+fn real_path<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+) -> NifResult<Term<'a>> {
+    let unc = unc_in_share(&conn.share, &path_in_share)?;
 
-            let attrs_bits: u32 = u32::from_le_bytes(basic.file_attributes.into_bytes());
-            let mtime = filetime_to_unix_seconds(*basic.last_write_time);
-            let atime = filetime_to_unix_seconds(*basic.last_access_time);
-            let ctime = filetime_to_unix_seconds(*basic.change_time);
-            let btime = filetime_to_unix_seconds(*basic.creation_time);
+    let access = FileAccessMask::new().with_file_read_attributes(true);
+    let args = FileCreateArgs::make_open_existing(access);
 
-            (stdi.end_of_file, stdi.allocation_size, stdi.number_of_links, attrs_bits, mtime, atime, ctime, btime)
+    let mut client = conn.lock_client()?;
+    let res = match client.create_file(&unc, &args) {
+        Ok(res) => res,
+        Err(e) if ntstatus_from_err_display(&e) == Some(STATUS_OBJECT_NAME_NOT_FOUND) => {
+            conn.note_status(&e);
+            return Err(rustler::Error::Term(Box::new(atoms::not_found())));
         }
-        Kind::Dir => {
-            let dir: Directory = res
-                .try_into()
-                .map_err(|_| rustler::Error::Term(Box::new("not_a_directory")))?;
-            let basic: FileBasicInformation = dir
-                .query_info()
-                .map_err(|e| rustler::Error::Term(Box::new(format!("query_basic_failed: {e}"))))?;
-            let stdi: FileStandardInformation = dir
-                .query_info()
-                .map_err(|e| rustler::Error::Term(Box::new(format!("query_standard_failed: {e}"))))?;
-
-            let attrs_bits: u32 = u32::from_le_bytes(basic.file_attributes.into_bytes());
-            let mtime = filetime_to_unix_seconds(*basic.last_write_time);
-            let atime = filetime_to_unix_seconds(*basic.last_access_time);
-            let ctime = filetime_to_unix_seconds(*basic.change_time);
-            let btime = filetime_to_unix_seconds(*basic.creation_time);
+        Err(e) => return Err(conn.create_error("smb_open_failed", &e)),
+    };
+    drop(client);
 
-            (stdi.end_of_file, stdi.allocation_size, stdi.number_of_links, attrs_bits, mtime, atime, ctime, btime)
+    let name_err = |e: smb::Error| conn.op_error("query_name_failed", &e);
+    let normalized: Result<FileNormalizedNameInformation, smb::Error> = match &res {
+        Resource::File(file) => file.query_info(),
+        Resource::Directory(dir) => dir.query_info(),
+        _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+    };
+    let name = match normalized {
+        Ok(info) => info.file_name.to_string(),
+        Err(e) if matches!(
+            ntstatus_from_err_display(&e),
+            Some(STATUS_NOT_SUPPORTED) | Some(STATUS_INVALID_PARAMETER) | Some(STATUS_INVALID_INFO_CLASS)
+        ) => {
+            let all: FileAllInformation = match &res {
+                Resource::File(file) => file.query_info().map_err(name_err)?,
+                Resource::Directory(dir) => dir.query_info().map_err(name_err)?,
+                _ => return Err(rustler::Error::Term(Box::new("not_a_file_or_dir"))),
+            };
+            all.name.file_name.to_string()
         }
+        Err(e) => return Err(name_err(e)),
     };
 
-    // Build map -> {:ok, map}
-    let out = RichStats {
-        r#type: match kind { Kind::File => atoms::file(), Kind::Dir => atoms::directory() },
-        size,
-        allocation_size: alloc,
-        nlink,
-        attributes: attrs_bits,
-        mtime,
-        atime,
-        ctime,
-        btime,
-    };
+    // \Dir\File -> Dir/File, like the paths callers pass in
+    let rel = name.trim_start_matches('\\').replace('\\', "/");
+    Ok((atoms::ok(), rel).encode(env))
+}
 
-    Ok((atoms::ok(), out).encode(env))
+/// Recursively deletes `path_in_share`, each directory's entries under a
+/// FILE_DELETE_CHILD open of it where the server grants one. Symlinks and junctions are
+/// removed as links; with `follow_symlinks` junctions/mount points are descended into
+/// and their target's contents deleted too (SMB symlinks are still only unlinked). With
+/// `dry_run` nothing is deleted and `{:ok, paths}` lists what would be, deepest first.
+///
+/// With `skip_offline`, files with FILE_ATTRIBUTE_OFFLINE (tiered away on an HSM share)
+/// and the directories containing them are left in place, and the result gets the
+/// offline paths as an extra element: `{:ok, offline}`, or `{:ok, paths, offline}`
+/// with `dry_run`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn rm_rf<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    path_in_share: String,
+    follow_symlinks: bool,
+    dry_run: bool,
+    skip_offline: bool,
+) -> NifResult<Term<'a>> {
+    let rel = path_in_share.trim_matches(['\\', '/']);
+    if rel.is_empty() {
+        return Err(rustler::Error::Term(Box::new("bad_path")));
+    }
+
+    let opts = RmOpts { follow: follow_symlinks, dry_run, skip_offline };
+    let mut out = RmOut::default();
+    rm_tree(&conn, rel, ChildDelete::OnClose, &opts, 0, &mut HashSet::new(), &mut out)?;
+    match (dry_run, skip_offline) {
+        (false, false) => Ok(atoms::ok().encode(env)),
+        (true, false) => Ok((atoms::ok(), out.paths).encode(env)),
+        (false, true) => Ok((atoms::ok(), out.offline).encode(env)),
+        (true, true) => Ok((atoms::ok(), out.paths, out.offline).encode(env)),
+    }
 }
 
+/// Renames/moves within the share. With `dry_run` only checks that the source exists
+/// and returns `{:ok, [{from, to}]}` (`{:error, :not_found}` otherwise).
 #[rustler::nif(schedule = "DirtyIo")]
 fn rename<'a>(
     env: Env<'a>,
@@ -604,6 +5178,7 @@ fn rename<'a>(
     from_in_share: String,
     to_in_share: String,
     replace_if_exists: bool,
+    dry_run: bool,
 ) -> NifResult<Term<'a>> {
     let from_rel = from_in_share.trim_matches(['\\', '/']);
     let to_rel   = to_in_share.trim_matches(['\\', '/']);
@@ -611,19 +5186,59 @@ fn rename<'a>(
         return Err(rustler::Error::Term(Box::new("bad_path")));
     }
 
+    if dry_run {
+        let unc = unc_in_share(&conn.share, from_rel)?;
+        let mut client = conn.lock_client()?;
+        return match open_for_kind(&mut client, &unc) {
+            Some(_) => Ok((atoms::ok(), vec![(from_rel, to_rel)]).encode(env)),
+            None => Err(rustler::Error::Term(Box::new(atoms::not_found()))),
+        };
+    }
+
+    rename_entry(&conn, from_rel, to_rel, replace_if_exists)?;
+    Ok(atoms::ok().encode(env))
+}
+
+/// Renames `old_name` to `new_name` inside `dir_in_share`; both are plain names
+/// (no separators). Fails if `new_name` already exists.
+#[rustler::nif(schedule = "DirtyIo")]
+fn rename_in_dir<'a>(
+    env: Env<'a>,
+    conn: ResourceArc<Conn>,
+    dir_in_share: String,
+    old_name: String,
+    new_name: String,
+) -> NifResult<Term<'a>> {
+    let plain = |n: &str| !n.is_empty() && n != "." && n != ".." && !n.contains(['\\', '/']);
+    if !plain(&old_name) || !plain(&new_name) {
+        return Err(rustler::Error::Term(Box::new("bad_name")));
+    }
+
+    // SMB2 rename targets are share-relative, so the directory is spelled out here
+    let dir = dir_in_share.trim_matches(['\\', '/']);
+    let in_dir = |n: &str| if dir.is_empty() { n.to_string() } else { format!(r"{dir}\{n}") };
+
+    rename_entry(&conn, &in_dir(&old_name), &in_dir(&new_name), false)?;
+    Ok(atoms::ok().encode(env))
+}
+
+// Opens `from_rel` (file first, then directory) and sets FileRenameInformation2
+// with the share-relative `to_rel`.
+fn rename_entry(conn: &Conn, from_rel: &str, to_rel: &str, replace_if_exists: bool) -> NifResult<()> {
+    conn.forget_handle(from_rel);
+    conn.forget_handle(to_rel);
+
     // Build full UNC
     let base = conn.share.to_string();
     let from_unc = format!(r"{}\{}", base.trim_end_matches('\\'), from_rel);
-    
+
     // Full RELATIVE destination path for file_name (share-relative)
     // SMB expects backslashes:
     let to_rel_bs = to_rel.replace('/', "\\");
     let file_name: SizedWideString = to_rel_bs.as_str().into();
 
     // Open source object to call set_file_info
-    let mut client = conn.client
-        .lock()
-        .map_err(|_| rustler::Error::Term(Box::new("mutex_poisoned")))?;
+    let mut client = conn.lock_client()?;
 
     let access = FileAccessMask::new()
         .with_delete(true)
@@ -640,37 +5255,47 @@ fn rename<'a>(
         file_name,
     };
 
+    // destination taken and !replace_if_exists — callers may retry with replace
+    let rename_err = |e: smb::Error| match ntstatus_from_err_display(&e) {
+        Some(STATUS_OBJECT_NAME_COLLISION) => {
+            conn.note_status(&e);
+            rustler::Error::Term(Box::new(atoms::target_exists()))
+        }
+        _ => conn.op_error("rename_failed", &e),
+    };
+
     // 1) try as file
     args.options = CreateOptions::default().with_non_directory_file(true);
     if let Ok(res) = client.create_file(&from_unc, &args) {
         let file: SmbFile = res
             .try_into()
             .map_err(|_| rustler::Error::Term(Box::new("not_a_file_or_dir")))?;
-        file.set_file_info(info)
-            .map_err(|e| rustler::Error::Term(Box::new(format!("rename_failed: {e}"))))?;
+        file.set_file_info(info).map_err(rename_err)?;
 
-        return Ok(atoms::ok().encode(env));
+        return Ok(());
     }
 
     // 2) otherwise as directory
     args.options = CreateOptions::default().with_directory_file(true);
     let res = client
         .create_file(&from_unc, &args)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("open_failed: {e}"))))?;
-    
+        .map_err(|e| conn.op_error("open_failed", &e))?;
+
     let dir: Directory = res
         .try_into()
-        .map_err(|_| rustler::Error::Term(Box::new("not_a_directory")))?;
-    dir.set_file_info(info)
-        .map_err(|e| rustler::Error::Term(Box::new(format!("rename_failed: {e}"))))?;
+        .map_err(|_| rustler::Error::Term(Box::new(atoms::not_a_directory())))?;
+    dir.set_file_info(info).map_err(rename_err)?;
 
-    Ok(atoms::ok().encode(env))
+    Ok(())
 }
 
 // ==================== on_load & init ====================
 
 fn on_load(env: Env, _info: Term) -> bool {
     let _ty = rustler::resource!(Conn, env);
+    let _ty = rustler::resource!(Session, env);
+    let _ty = rustler::resource!(FileHandle, env);
+    let _ty = rustler::resource!(CancelToken, env);
     true
 }
 
@@ -678,3 +5303,281 @@ rustler::init!(
     "Elixir.Rumbex.Native",
     load = on_load
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nt_hash_password_layout() {
+        let hash: Vec<u8> = (0u8..16).map(|i| i * 17).collect();
+        let bytes: Vec<u8> = nt_hash_password(&hash)
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(bytes.len(), 32 + NT_HASH_PADDING_BYTES);
+        assert_eq!(&bytes[..32], b"00112233445566778899AABBCCDDEEFF");
+        assert!(bytes[32..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn share_root_spellings() {
+        assert!(is_share_root(""));
+        assert!(is_share_root("/"));
+        assert!(is_share_root("\\"));
+        assert!(is_share_root("\\/\\"));
+        assert!(!is_share_root("dir"));
+        assert!(!is_share_root("\\dir\\"));
+    }
+
+    #[test]
+    fn neutral_open_options_are_clear() {
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::new());
+        assert!(!args.options.directory_file());
+        assert!(!args.options.non_directory_file());
+    }
+
+    #[test]
+    fn kind_from_refused_open() {
+        assert_eq!(kind_of_status(STATUS_FILE_IS_A_DIRECTORY), Some(Kind::Dir));
+        assert_eq!(kind_of_status(STATUS_NOT_A_DIRECTORY), Some(Kind::File));
+        assert_eq!(kind_of_status(STATUS_STOPPED_ON_SYMLINK), Some(Kind::Symlink));
+        assert_eq!(kind_of_status(STATUS_ACCESS_DENIED), None);
+
+        // a directory the server won't open neutrally gets the explicit directory open
+        assert!(retry_as_directory(Some(STATUS_INVALID_PARAMETER)));
+        assert!(retry_as_directory(Some(STATUS_ACCESS_DENIED)));
+        assert!(!retry_as_directory(Some(STATUS_OBJECT_NAME_NOT_FOUND)));
+        assert!(!retry_as_directory(Some(STATUS_OBJECT_PATH_NOT_FOUND)));
+        assert!(!retry_as_directory(Some(STATUS_FILE_IS_A_DIRECTORY)));
+        assert!(!retry_as_directory(None));
+    }
+
+    #[test]
+    fn open_bits_validation() {
+        assert_eq!(validate_open_bits(FILE_GENERIC_READ, 0), Ok(()));
+        assert_eq!(validate_open_bits(FILE_ALL_ACCESS, OPT_DIRECTORY_FILE), Ok(()));
+        assert_eq!(validate_open_bits(0, 0), Err("bad_access"));
+        assert_eq!(validate_open_bits(FILE_GENERIC_READ | 0x0000_0200, 0), Err("bad_access"));
+        assert_eq!(validate_open_bits(FILE_GENERIC_READ, 0x0100_0000), Err("bad_options"));
+        let both = OPT_DIRECTORY_FILE | OPT_NON_DIRECTORY_FILE;
+        assert_eq!(validate_open_bits(FILE_GENERIC_READ, both), Err("bad_options"));
+        // delete-on-close needs DELETE access
+        assert_eq!(validate_open_bits(FILE_GENERIC_WRITE, OPT_DELETE_ON_CLOSE), Err("bad_options"));
+        let with_delete = FILE_GENERIC_WRITE | ACCESS_DELETE;
+        assert_eq!(validate_open_bits(with_delete, OPT_DELETE_ON_CLOSE), Ok(()));
+    }
+
+    #[test]
+    fn share_parsing() {
+        let unc = parse_share(r"\\host\share").ok().unwrap();
+        assert_eq!(unc.to_string(), r"\\host\share");
+        let url_style = parse_share("//host/share").ok().unwrap();
+        assert_eq!(url_style.to_string(), unc.to_string());
+        assert!(parse_share("").is_err());
+    }
+
+    #[test]
+    fn sid_parsing() {
+        let sid = parse_sid("S-1-5-21-1004336348-1177238915-682003330-512").unwrap();
+        assert_eq!(sid.to_string(), "S-1-5-21-1004336348-1177238915-682003330-512");
+        assert!(parse_sid("S-1-5-32-544").is_some());
+        assert!(parse_sid("S-1-5").is_none()); // no sub-authority
+        assert!(parse_sid("S-1-5-1-2-3-4-5-6-7-8-9-10-11-12-13-14-15-16").is_none());
+        assert!(parse_sid("S-1-281474976710656-1").is_none()); // authority past 48 bits
+        assert!(parse_sid("S-1-5-4294967296").is_none()); // sub-authority past u32
+        assert!(parse_sid("S-1-5-+1").is_none());
+        assert!(parse_sid("S-2-5-32-544").is_none());
+        assert!(parse_sid("not a sid").is_none());
+    }
+
+    #[test]
+    fn temp_sibling_stays_in_the_directory() {
+        let a = temp_sibling("dir/sub/name.txt");
+        assert!(a.starts_with(r"dir\sub\.name.txt.tmp."), "{a}");
+        assert!(temp_sibling("name.txt").starts_with(".name.txt.tmp."));
+        assert_ne!(temp_sibling("dir/name"), temp_sibling("dir/name"));
+    }
+
+    #[test]
+    fn cache_key_normalizes() {
+        assert_eq!(cache_key("/Dir/Sub/A.TXT"), r"dir\sub\a.txt");
+        assert_eq!(cache_key(r"\dir\sub\a.txt\"), r"dir\sub\a.txt");
+        assert_eq!(cache_key("dir/sub/a.txt"), cache_key(r"DIR\SUB\A.txt"));
+    }
+
+    #[test]
+    fn child_delete_modes_open_alike() {
+        let file = TreeEntry { dir: false, reparse_tag: None, offline: false, dir_id: 0 };
+        let junction = Some(0xA000_0003); // IO_REPARSE_TAG_MOUNT_POINT
+        let link = TreeEntry { dir: true, reparse_tag: junction, offline: false, dir_id: 0 };
+        for entry in [&file, &link] {
+            let on_close = delete_args(entry, ChildDelete::OnClose);
+            let disposition = delete_args(entry, ChildDelete::Disposition);
+            assert!(on_close.options.delete_on_close());
+            assert!(!disposition.options.delete_on_close());
+            // the same object is opened, with the same DELETE access, either way
+            let same = on_close.options.with_delete_on_close(false);
+            assert_eq!(same.into_bytes(), disposition.options.into_bytes());
+            assert_eq!(
+                on_close.desired_access.into_bytes(),
+                disposition.desired_access.into_bytes()
+            );
+            assert!(disposition.desired_access.delete());
+            assert_eq!(disposition.options.directory_file(), entry.dir);
+            assert_eq!(disposition.options.open_reparse_point(), entry.reparse_tag.is_some());
+        }
+    }
+
+    #[test]
+    fn ntstatus_from_smb_errors() {
+        let named = smb::Error::UnexpectedMessageStatus(STATUS_OBJECT_NAME_NOT_FOUND);
+        assert_eq!(ntstatus_from_err_display(&named), Some(STATUS_OBJECT_NAME_NOT_FOUND));
+        let unnamed = smb::Error::UnexpectedMessageStatus(0x1234_5678);
+        assert_eq!(ntstatus_from_err_display(&unnamed), Some(0x1234_5678));
+        // wrapped in our own context, as op_error and the io::Error conversions do
+        let wrapped = format!("smb_read_failed: {named}");
+        assert_eq!(ntstatus_from_err_display(&wrapped), Some(STATUS_OBJECT_NAME_NOT_FOUND));
+        assert_eq!(ntstatus_from_err_display(&"connection reset"), None);
+        assert_eq!(ntstatus_from_err_display(&"value 0x123456789abc"), None);
+    }
+
+    // what the QUERY_DIRECTORY iterator yields across rounds, flattened
+    fn round(names: &[&str]) -> Vec<Result<SizedWideString, smb::Error>> {
+        names.iter().map(|&n| Ok(SizedWideString::from(n))).collect()
+    }
+
+    fn walk(records: Vec<Result<SizedWideString, smb::Error>>) -> (Vec<String>, u64, bool) {
+        let mut names = Vec::new();
+        let (skipped, failed) = walk_records(records, |n| n, |name, _| names.push(name));
+        (names, skipped, failed.is_some())
+    }
+
+    #[test]
+    fn enumeration_spans_rounds() {
+        let bad_name = SizedWideString::from_iter([0xD800u16, 0x0041]); // lone surrogate
+        let mut records = round(&[".", "..", "a.txt", "sub"]);
+        records.push(Ok(bad_name));
+        records.extend(round(&["b.txt", "c.txt"]));
+        // a record the client couldn't parse: no status, skipped and counted
+        records.push(Err(smb::Error::InvalidMessage("bad record".to_string())));
+        records.extend(round(&["d.txt"]));
+        records.push(Err(smb::Error::UnexpectedMessageStatus(STATUS_NO_MORE_FILES)));
+        let (names, skipped, failed) = walk(records);
+        assert_eq!(names, ["a.txt", "sub", "b.txt", "c.txt", "d.txt"]);
+        assert_eq!(skipped, 2);
+        assert!(!failed);
+
+        // a later round failing keeps what the earlier rounds returned
+        let mut records = round(&["a.txt", "b.txt"]);
+        records.push(Err(smb::Error::UnexpectedMessageStatus(STATUS_ACCESS_DENIED)));
+        records.extend(round(&["never.txt"]));
+        let (names, skipped, failed) = walk(records);
+        assert_eq!(names, ["a.txt", "b.txt"]);
+        assert_eq!(skipped, 0);
+        assert!(failed);
+
+        // a pattern that matches nothing ends on NO_SUCH_FILE, not as a failure
+        let records = vec![Err(smb::Error::UnexpectedMessageStatus(STATUS_NO_SUCH_FILE))];
+        assert_eq!(walk(records), (Vec::new(), 0, false));
+    }
+
+    // An in-memory file taking at most `max` bytes per READ/WRITE, as a server caps them.
+    #[derive(Clone)]
+    struct MemFile {
+        data: Arc<Mutex<Vec<u8>>>,
+        max: usize,
+    }
+
+    impl BlockIo for MemFile {
+        fn read_block_at(&self, buf: &mut [u8], pos: u64) -> std::io::Result<usize> {
+            let data = self.data.lock().unwrap();
+            let pos = pos as usize;
+            let n = buf.len().min(self.max).min(data.len().saturating_sub(pos));
+            buf[..n].copy_from_slice(&data[pos..pos + n]);
+            Ok(n)
+        }
+
+        fn write_block_at(&self, chunk: &[u8], pos: u64) -> std::io::Result<usize> {
+            let mut data = self.data.lock().unwrap();
+            let (pos, n) = (pos as usize, chunk.len().min(self.max));
+            if data.len() < pos + n {
+                data.resize(pos + n, 0);
+            }
+            data[pos..pos + n].copy_from_slice(&chunk[..n]);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn stripes_round_trip() {
+        let payload: Vec<u8> = (0..100_003u32).map(|i| (i * 7 % 251) as u8).collect();
+        let file = MemFile { data: Arc::default(), max: 4096 };
+        let open = |_| Ok(file.clone());
+
+        write_stripes(&payload, 4, &file, open).unwrap();
+        assert!(*file.data.lock().unwrap() == payload);
+
+        let mut back = vec![0; payload.len()];
+        read_stripes(&mut back, 4, &file, open).unwrap();
+        assert!(back == payload);
+
+        // a later stripe reaching past the end fails the read instead of leaving zeros
+        let mut longer = vec![0; payload.len() + 10];
+        assert!(read_stripes(&mut longer, 4, &file, open).is_err());
+        let failing = |i| if i == 1 { Err("smb_open_failed".to_string()) } else { open(i) };
+        assert!(read_stripes(&mut back, 4, &file, failing).is_err());
+    }
+
+    #[test]
+    fn cached_handle_rereads_from_start() {
+        // read_whole hands a cached handle out again after a full read; the next read of
+        // the same path must start at 0, not where the previous one stopped
+        let content = b"the same path, read twice".to_vec();
+        let file = MemFile { data: Arc::new(Mutex::new(content.clone())), max: 8 };
+        for _ in 0..2 {
+            let mut buf = vec![0; content.len()];
+            assert_eq!(read_up_to_at(&file, &mut buf, 0).unwrap(), content.len());
+            assert!(buf == content);
+        }
+        // and a file that ends early comes back short, for read_whole's short_read check
+        let mut longer = vec![0; content.len() + 4];
+        assert_eq!(read_up_to_at(&file, &mut longer, 0).unwrap(), content.len());
+    }
+
+    #[test]
+    fn read_cap_extremes() {
+        assert!(!exceeds_read_cap(u64::MAX, None));
+        assert!(!exceeds_read_cap(0, Some(0)));
+        assert!(exceeds_read_cap(1, Some(0)));
+        assert!(!exceeds_read_cap(u64::MAX, Some(u64::MAX)));
+        assert!(exceeds_read_cap(u64::MAX, Some(u64::MAX - 1)));
+        assert!(!exceeds_read_cap(1 << 20, Some(1 << 20)));
+        assert!(exceeds_read_cap((1 << 20) + 1, Some(1 << 20)));
+    }
+
+    #[test]
+    fn filetime_extremes() {
+        const EPOCH_TICKS: u64 = 11_644_473_600 * 10_000_000;
+        assert_eq!(filetime_to_unix_seconds(0), 0);
+        assert_eq!(filetime_to_unix_seconds(FILETIME_NO_CHANGE), 0);
+        assert_eq!(filetime_to_unix_seconds(1), 0); // before 1970
+        assert_eq!(filetime_to_unix_seconds(EPOCH_TICKS), 0);
+        assert_eq!(filetime_to_unix_seconds(EPOCH_TICKS + 10_000_000), 1);
+        // far-future values clamp to the largest valid FILETIME instead of wrapping
+        let max = filetime_to_unix_seconds(FILETIME_MAX);
+        assert_eq!(filetime_to_unix_seconds(u64::MAX - 1), max);
+        assert_eq!(filetime_to_unix_seconds(FILETIME_MAX + 1), max);
+
+        assert_eq!(filetime_to_unix_nanos(0), UnixNanos::Unknown);
+        assert_eq!(filetime_to_unix_nanos(FILETIME_NO_CHANGE), UnixNanos::NoChange);
+        assert_eq!(filetime_to_unix_nanos(1), UnixNanos::At(0));
+        assert_eq!(filetime_to_unix_nanos(EPOCH_TICKS + 1), UnixNanos::At(100));
+        assert_eq!(filetime_to_unix_nanos(u64::MAX - 1), UnixNanos::At(u64::MAX));
+
+        // round trip through SET_INFO's conversion
+        let t = UnixNanos::At(1_700_000_000_123_456_700);
+        assert_eq!(filetime_to_unix_nanos(unix_nanos_to_filetime(t)), t);
+        assert_eq!(unix_nanos_to_filetime(UnixNanos::At(u64::MAX)), u64::MAX / 100 + EPOCH_TICKS);
+    }
+}
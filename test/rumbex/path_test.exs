@@ -0,0 +1,33 @@
+defmodule Rumbex.PathTest do
+  use ExUnit.Case, async: true
+
+  alias Rumbex.Path
+
+  describe "parse_smb_url!/1" do
+    test "percent-encoded and literal admin shares name the same share" do
+      assert Path.parse_smb_url!("smb://host/C%24/dir/a.txt") == {"\\\\host\\C$", "dir/a.txt"}
+      assert Path.parse_smb_url!("smb://host/C$/dir/a.txt") == {"\\\\host\\C$", "dir/a.txt"}
+    end
+
+    test "smb url without a path inside the share" do
+      assert Path.parse_smb_url!("smb://host/share") == {"\\\\host\\share", ""}
+      assert Path.parse_smb_url!("smb://host/share/") == {"\\\\host\\share", ""}
+    end
+
+    test "URL-style UNC" do
+      assert Path.parse_smb_url!("//host/share") == {"\\\\host\\share", ""}
+      assert Path.parse_smb_url!("//host/share/dir/a.txt") == {"\\\\host\\share", "dir/a.txt"}
+    end
+
+    test "backslash UNC" do
+      assert Path.parse_smb_url!("\\\\host\\share\\dir\\a.txt") ==
+               {"\\\\host\\share", "dir/a.txt"}
+    end
+
+    test "missing share raises" do
+      assert_raise ArgumentError, fn -> Path.parse_smb_url!("smb://host") end
+      assert_raise ArgumentError, fn -> Path.parse_smb_url!("smb://host/") end
+      assert_raise ArgumentError, fn -> Path.parse_smb_url!("\\\\host") end
+    end
+  end
+end
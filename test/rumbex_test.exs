@@ -0,0 +1,27 @@
+defmodule RumbexTest do
+  use ExUnit.Case, async: true
+
+  @label "@GMT-2024.05.01-12.00.00"
+
+  describe "snapshot_path/2" do
+    test "prefixes the share-relative path with the label" do
+      assert Rumbex.snapshot_path("/dir/a.txt", @label) == @label <> "/dir/a.txt"
+      assert Rumbex.snapshot_path("\\dir\\a.txt", @label) == @label <> "/dir/a.txt"
+      assert Rumbex.snapshot_path("", @label) == @label <> "/"
+    end
+
+    test "only takes @GMT- labels" do
+      assert_raise FunctionClauseError, fn -> Rumbex.snapshot_path("/a", "2024.05.01") end
+    end
+  end
+
+  describe ":snapshot option" do
+    # rejected before any connection is attempted, so no server is needed
+    test "malformed labels are :bad_snapshot" do
+      for label <- ["@GMT-2024.05.01", "@GMT-2024.05.01-12.00.00/x", "2024.05.01-12.00.00", 42] do
+        assert Rumbex.list_dir("smb://host/share", "u", "p", "/", snapshot: label) ==
+                 {:error, :bad_snapshot}
+      end
+    end
+  end
+end